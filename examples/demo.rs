@@ -0,0 +1,196 @@
+use std::path::PathBuf;
+
+use glam::uvec2;
+use normal_map_explosion::{components::{Sprite, SpriteAnimation}, headless, render::{AssetManager, CameraController, Tonemapper}, scene::DEFAULT_SCENE, Renderer, Scene, SceneDescription};
+use winit::{window::{Window, WindowBuilder}, dpi::PhysicalSize, event_loop::{ControlFlow, EventLoop}, event::{Event, WindowEvent}};
+
+const INITIAL_SCREEN_SIZE: PhysicalSize<u32> = PhysicalSize::new(1280, 720);
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let headless_output = args.iter()
+        .position(|arg| arg == "--headless")
+        .map(|i| args.get(i + 1).cloned().unwrap_or_else(|| "frame.png".to_string()));
+
+    let sprite_count = args.iter()
+        .position(|arg| arg == "--sprites")
+        .and_then(|i| args.get(i + 1))
+        .map(|count| count.parse::<usize>().expect("--sprites expects a number"));
+
+    let sprite_sheet = args.iter()
+        .position(|arg| arg == "--sheet")
+        .map(|i| args.get(i + 1).expect("--sheet expects a path to an Aseprite JSON export"))
+        .map(|path| std::path::absolute(path).unwrap().to_string_lossy().into_owned());
+
+    let assets = args.iter()
+        .position(|arg| arg == "--assets")
+        .map(|i| AssetManager::new(args.get(i + 1).expect("--assets expects a directory")))
+        .unwrap_or_default();
+
+    let hot_reload = args.iter().any(|arg| arg == "--watch");
+    let filtering = args.iter().any(|arg| arg == "--filtering");
+    let bloom = args.iter().any(|arg| arg == "--bloom");
+
+    let scene_path = args.iter()
+        .position(|arg| arg == "--scene")
+        .map(|i| PathBuf::from(args.get(i + 1).expect("--scene expects a .ron or .json path")));
+
+    let save_scene = args.iter()
+        .position(|arg| arg == "--save-scene")
+        .map(|i| PathBuf::from(args.get(i + 1).expect("--save-scene expects a .ron or .json path")));
+
+    let tonemapper = args.iter()
+        .position(|arg| arg == "--tonemap")
+        .map(|i| match args.get(i + 1).map(String::as_str) {
+            Some("none") => Tonemapper::None,
+            Some("reinhard") => Tonemapper::Reinhard,
+            Some("aces") => Tonemapper::Aces,
+            _ => panic!("--tonemap expects none, reinhard or aces")
+        });
+
+    let exposure = args.iter()
+        .position(|arg| arg == "--exposure")
+        .and_then(|i| args.get(i + 1))
+        .map(|stops| stops.parse::<f32>().expect("--exposure expects a number of stops"));
+
+    let scene_path = scene_path.unwrap_or_else(|| assets.resolve(DEFAULT_SCENE));
+    let mut description = SceneDescription::load(&scene_path).unwrap_or_else(|error| panic!("{}", error));
+    if sprite_sheet.is_some() {
+        description.sprite_sheet = sprite_sheet;
+    }
+    if let Some(tonemapper) = tonemapper {
+        description.tonemap.tonemapper = tonemapper;
+    }
+    if let Some(exposure) = exposure {
+        description.tonemap.exposure = exposure;
+    }
+    if bloom {
+        description.bloom.enabled = true;
+    }
+
+    let builder = Renderer::builder(uvec2(INITIAL_SCREEN_SIZE.width, INITIAL_SCREEN_SIZE.height))
+        .with_assets(assets)
+        .with_hot_reload(hot_reload)
+        .with_filtering(filtering);
+
+    match headless_output {
+        Some(path) => {
+            let mut renderer = futures::executor::block_on(builder.build_headless())
+                .expect("Failed to initialize headless renderer");
+            let mut scene = Scene::load(&mut renderer, description).unwrap_or_else(|error| panic!("{}", error));
+            if let Some(count) = sprite_count {
+                scene.fill_sprite_grid(count);
+            }
+            if let Some(path) = save_scene {
+                scene.describe().save(&path).unwrap();
+            }
+            renderer.prepare(&mut scene);
+            match headless::render_to_png(&renderer, &path) {
+                Ok(()) => println!("Wrote {}x{} frame to {}", INITIAL_SCREEN_SIZE.width, INITIAL_SCREEN_SIZE.height, path),
+                Err(error) => {
+                    eprintln!("Failed to write {}: {}", path, error);
+                    std::process::exit(1);
+                }
+            }
+        },
+        None => {
+            let (event_loop, window) = create_window();
+            let mut renderer = futures::executor::block_on(builder.build_for_window(&window))
+                .expect("Failed to initialize renderer");
+            window.set_visible(true);
+            let mut scene = Scene::load(&mut renderer, description).unwrap_or_else(|error| panic!("{}", error));
+            if let Some(count) = sprite_count {
+                scene.fill_sprite_grid(count);
+            }
+            if !scene.sprite_sheet_clips.is_empty() {
+                create_animated_sprite(&mut scene);
+            }
+            if let Some(path) = save_scene {
+                scene.describe().save(&path).unwrap();
+            }
+            renderer.prepare(&mut scene);
+            run_loop(event_loop, window, renderer, scene);
+        }
+    }
+}
+
+/// Adds a sprite playing the first clip of the loaded sprite sheet, drawn over the first scene sprite.
+fn create_animated_sprite(scene: &mut Scene) {
+    let clips = scene.sprite_sheet_clips.clone();
+    let first = clips[0].name.clone();
+    let mut animation = SpriteAnimation::new(clips);
+    animation.play(&first);
+
+    let mut sprite = Sprite::from_region(&scene.atlas, animation.current_region().unwrap()).unwrap();
+    sprite.transform = scene.description.sprites.first().map(|sprite| sprite.transform.to_transform()).unwrap_or_default();
+    sprite.material = scene.atlas_material;
+    sprite.animation = Some(animation);
+    scene.sprites.push(sprite);
+}
+
+fn create_window() -> (EventLoop<()>, Window) {
+    let event_loop = EventLoop::with_user_event();
+    let window = WindowBuilder::new()
+        .with_visible(false)
+        .with_title("Draw Something")
+        .build(&event_loop)
+        .unwrap();
+    if window.fullscreen().is_none() {
+        window.set_inner_size(INITIAL_SCREEN_SIZE);
+    }
+    (event_loop, window)
+}
+
+fn run_loop(event_loop: EventLoop<()>, window: Window, mut renderer: Renderer, mut scene: Scene) {
+    let mut last_update: Option<std::time::Instant> = None;
+    let mut controller = CameraController::default();
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        // The surface must not outlive the window it was created from.
+        let _ = &window;
+
+        match event {
+            Event::NewEvents(_) => {
+            },
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,..} => *control_flow = ControlFlow::Exit,
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                renderer.resize(uvec2(size.width, size.height));
+                renderer.prepare(&mut scene);
+            },
+            Event::WindowEvent {
+                event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                ..
+            } => {
+                renderer.resize(uvec2(new_inner_size.width, new_inner_size.height));
+                renderer.prepare(&mut scene);
+            },
+            Event::WindowEvent { event, .. } => {
+                controller.handle_event(&event, &mut scene.camera);
+            },
+            Event::MainEventsCleared => {
+                let now = std::time::Instant::now();
+                let dt = last_update.map_or(0.0, |last| (now - last).as_secs_f32());
+                last_update = Some(now);
+                if scene.update_animations(dt) {
+                    renderer.prepare_sprites(&scene);
+                }
+                controller.update(&mut scene.camera, dt);
+                renderer.prepare_camera(&mut scene);
+                renderer.hot_reload(&mut scene);
+                match renderer.render_frame() {
+                    Ok(()) | Err(wgpu::SurfaceError::Timeout) => {},
+                    Err(wgpu::SurfaceError::OutOfMemory) => {
+                        eprintln!("out of GPU memory");
+                        *control_flow = ControlFlow::Exit;
+                    },
+                    Err(error) => eprintln!("{}", error)
+                }
+            },
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,38 @@
+//! Draws the default scene with a device and target owned by the
+//! application, as an app with its own wgpu setup would.
+
+use std::sync::Arc;
+
+use glam::uvec2;
+use normal_map_explosion::{render::Texture, scene::DEFAULT_SCENE, Renderer, Scene, SceneDescription};
+
+fn main() {
+    let size = uvec2(640, 360);
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    let instance = wgpu::Instance::new(wgpu::Backends::all());
+    let adapter = futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+        .expect("no adapter available");
+    let (device, queue) = futures::executor::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        label: None,
+        features: wgpu::Features::empty(),
+        limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
+    }, None)).unwrap();
+    let (device, queue) = (Arc::new(device), Arc::new(queue));
+    let target = Texture::create_render_target(&device, size, format, "app_target");
+
+    let mut renderer = Renderer::builder(size)
+        .build_with_device(&adapter, device.clone(), queue.clone(), format)
+        .unwrap();
+    let description = SceneDescription::load(&renderer.assets.resolve(DEFAULT_SCENE)).unwrap();
+    let mut scene = Scene::load(&mut renderer, description).unwrap();
+    renderer.prepare(&mut scene);
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("app_encoder") });
+    // The application's own passes would be recorded into the same encoder here.
+    renderer.render(&target.view, &mut encoder);
+    queue.submit(core::iter::once(encoder.finish()));
+
+    target.read_to_image(&device, &queue).save("embedded.png").unwrap();
+    println!("Wrote embedded.png");
+}
@@ -0,0 +1,72 @@
+// Bloom chain: the prefilter keeps what is brighter than the threshold at
+// half resolution, downsample blurs it into ever smaller levels, and
+// upsample adds each level back onto the one above it.
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+struct BloomSettings {
+    threshold: f32;
+    knee: f32;
+};
+
+[[group(0), binding(0)]]
+var source_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var source_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> settings: BloomSettings;
+
+fn source_texel() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(source_texture));
+}
+
+fn tap(uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+    return textureSample(source_texture, source_sampler, uv + offset * source_texel()).rgb;
+}
+
+// Four bilinear taps around the center, each averaging a 2x2 block.
+fn box_blur(uv: vec2<f32>) -> vec3<f32> {
+    return (tap(uv, vec2<f32>(-1.0, -1.0)) + tap(uv, vec2<f32>(1.0, -1.0))
+        + tap(uv, vec2<f32>(-1.0, 1.0)) + tap(uv, vec2<f32>(1.0, 1.0))) * 0.25;
+}
+
+[[stage(fragment)]]
+fn fs_prefilter(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Clamped so a single overflowing texel can't spread infinity.
+    let color = min(box_blur(in.uv), vec3<f32>(65000.0));
+    let brightness = max(color.r, max(color.g, color.b));
+    // Quadratic soft knee below the threshold.
+    let knee = max(settings.knee, 0.0001);
+    let soft = clamp(brightness - settings.threshold + knee, 0.0, 2.0 * knee);
+    let contribution = max(soft * soft / (4.0 * knee), brightness - settings.threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_downsample(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let center = tap(in.uv, vec2<f32>(0.0, 0.0));
+    return vec4<f32>((center * 4.0 + box_blur(in.uv) * 4.0) * 0.125, 1.0);
+}
+
+// 3x3 tent filter over the smaller level, added onto the target by blending.
+[[stage(fragment)]]
+fn fs_upsample(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let corners = tap(in.uv, vec2<f32>(-1.0, -1.0)) + tap(in.uv, vec2<f32>(1.0, -1.0))
+        + tap(in.uv, vec2<f32>(-1.0, 1.0)) + tap(in.uv, vec2<f32>(1.0, 1.0));
+    let edges = tap(in.uv, vec2<f32>(0.0, -1.0)) + tap(in.uv, vec2<f32>(-1.0, 0.0))
+        + tap(in.uv, vec2<f32>(1.0, 0.0)) + tap(in.uv, vec2<f32>(0.0, 1.0));
+    let center = tap(in.uv, vec2<f32>(0.0, 0.0));
+    return vec4<f32>((corners + edges * 2.0 + center * 4.0) / 16.0, 1.0);
+}
@@ -2,6 +2,8 @@ struct FragmentInput {
     [[builtin(position)]] position: vec4<f32>;
     [[location(0)]] frag_position: vec3<f32>;
     [[location(1)]] uv: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+    [[location(3)]] tangent: vec4<f32>;
 };
 
 struct BaseLight
@@ -18,34 +20,276 @@ struct Attenuation
     exp: f32;
 };
 
+// Laid out like the std140 GPUPointLight: std140 pads the vec3 position to
+// 16 bytes, so atten starts at offset 48.
 struct PointLight
 {
     base: BaseLight;
-    position: vec4<f32>;
+    position: vec3<f32>;
+    position_padding: f32;
     atten: Attenuation;
 };
 
+struct DirectionalLight
+{
+    base: BaseLight;
+    direction: vec3<f32>;
+};
+
+struct SpotLight
+{
+    base: BaseLight;
+    position: vec3<f32>;
+    atten: Attenuation;
+    direction: vec3<f32>;
+    inner_cutoff: f32;
+    outer_cutoff: f32;
+};
+
+// Replaced at pipeline creation with the cap derived from the device limits.
+let MAX_POINT_LIGHTS: u32 = 256u;
+let MAX_DIRECTIONAL_LIGHTS: u32 = 4u;
+let MAX_SPOT_LIGHTS: u32 = 32u;
+
+struct PointLights
+{
+    lights: array<PointLight, MAX_POINT_LIGHTS>;
+};
+
+struct DirectionalLights
+{
+    lights: array<DirectionalLight, MAX_DIRECTIONAL_LIGHTS>;
+};
+
+struct SpotLights
+{
+    lights: array<SpotLight, MAX_SPOT_LIGHTS>;
+};
+
+struct AmbientLight
+{
+    sky_color: vec3<f32>;
+    intensity: f32;
+    ground_color: vec3<f32>;
+    up: vec3<f32>;
+};
+
+struct SceneLights
+{
+    count: u32;
+    directional_count: u32;
+    spot_count: u32;
+    ambient: AmbientLight;
+};
+
+struct LightGrid
+{
+    tiles: vec2<u32>;
+    tile_size: vec2<f32>;
+    target_size: vec2<f32>;
+    max_lights_per_tile: u32;
+    cutoff: f32;
+};
+
+struct DebugSettings
+{
+    view: u32;
+};
+
+[[group(0), binding(1)]]
+var<uniform> debug: DebugSettings;
+
+struct Eye
+{
+    position: vec3<f32>;
+    view_direction: vec3<f32>;
+};
+
+[[group(0), binding(2)]]
+var<uniform> eye: Eye;
+
 [[group(1), binding(0)]] var albedo_texture: texture_2d<f32>;
 [[group(1), binding(1)]] var normal_texture: texture_2d<f32>;
 [[group(1), binding(2)]] var texture_sampler: sampler;
 [[group(1), binding(3)]] var normal_sampler: sampler;
 
+struct Material
+{
+    tint: vec3<f32>;
+    specular_power: f32;
+    specular_intensity: f32;
+    flip_green: u32;
+    reconstruct_z: u32;
+    emissive: vec3<f32>;
+};
+
+[[group(1), binding(4)]]
+var<uniform> material: Material;
+
+// Red scales the specular intensity and green (gloss) the power. Materials
+// without a map bind a white texture.
+[[group(1), binding(5)]] var specular_texture: texture_2d<f32>;
+// Scaled by the material's emissive color and added to the lit color, so it
+// shows in the dark. Materials without a map bind a white texture.
+[[group(1), binding(6)]] var emissive_texture: texture_2d<f32>;
+
+struct SpecularAttributes
+{
+    power: f32;
+    intensity: f32;
+};
+
+struct Lighting
+{
+    diffuse: vec3<f32>;
+    specular: vec3<f32>;
+};
+
 [[group(2), binding(1)]]
-var<uniform> point_light: PointLight;
+var<uniform> point_lights: PointLights;
+
+[[group(2), binding(2)]]
+var<uniform> scene_lights: SceneLights;
+
+[[group(2), binding(3)]]
+var<uniform> directional_lights: DirectionalLights;
+
+[[group(2), binding(4)]]
+var<uniform> spot_lights: SpotLights;
+
+// tile_light_count and tile_light_index are declared by the light tile source
+// prepended for the active LightCullingMode.
+[[group(3), binding(0)]]
+var<uniform> light_grid: LightGrid;
+
+fn light_tile(position: vec2<f32>) -> u32 {
+    let tile = min(vec2<u32>(position / light_grid.tile_size), light_grid.tiles - vec2<u32>(1u));
+    return tile.y * light_grid.tiles.x + tile.x;
+}
+
+// Unpacks a sampled normal into a unit tangent-space vector with green pointing up the texture.
+fn decode_normal(sample: vec3<f32>) -> vec3<f32> {
+    var normal = sample * 2.0 - 1.0;
+    if (material.flip_green != 0u) {
+        normal.y = -normal.y;
+    }
+    if (material.reconstruct_z != 0u) {
+        normal.z = sqrt(max(1.0 - dot(normal.xy, normal.xy), 0.0));
+    }
+    return normalize(normal);
+}
+
+// Builds the tangent to world basis from the interpolated vertex frame.
+fn tbn(in: FragmentInput) -> mat3x3<f32> {
+    let n = normalize(in.normal);
+    let t = normalize(in.tangent.xyz - n * dot(n, in.tangent.xyz));
+    let b = cross(n, t) * in.tangent.w;
+    return mat3x3<f32>(t, b, n);
+}
+
+fn attenuate(atten: Attenuation, distance: f32) -> f32 {
+    return 1.0 / (atten.constant + atten.linear * distance + atten.exp * distance * distance);
+}
+
+fn scale_lighting(lighting: Lighting, amount: f32) -> Lighting {
+    return Lighting(lighting.diffuse * amount, lighting.specular * amount);
+}
+
+// Ambient and Lambert diffuse plus a Blinn-Phong highlight. `direction` points
+// from the fragment towards the light and `view` from the fragment towards the eye.
+fn base_light_shade(base: BaseLight, normal: vec3<f32>, direction: vec3<f32>, view: vec3<f32>, specular: SpecularAttributes) -> Lighting {
+    let radiance = base.color * base.diffuse_intensity;
+    let max_dot = max(dot(normal, direction), 0.0);
+    var highlight = 0.0;
+    if (max_dot > 0.0) {
+        let halfway = normalize(direction + view);
+        highlight = specular.intensity * pow(max(dot(normal, halfway), 0.0), specular.power);
+    }
+    let ambient = base.color * base.ambient_intensity;
+    return Lighting(ambient + radiance * max_dot, radiance * highlight);
+}
+
+fn scene_ambient(ambient: AmbientLight, normal: vec3<f32>) -> vec3<f32> {
+    let sky_amount = dot(normal, ambient.up) * 0.5 + 0.5;
+    return mix(ambient.ground_color, ambient.sky_color, sky_amount) * ambient.intensity;
+}
+
+fn point_light_shade(light: PointLight, normal: vec3<f32>, view: vec3<f32>, specular: SpecularAttributes, frag_position: vec3<f32>) -> Lighting {
+    let to_light = light.position - frag_position;
+    let lighting = base_light_shade(light.base, normal, normalize(to_light), view, specular);
+    return scale_lighting(lighting, attenuate(light.atten, length(to_light)));
+}
+
+fn directional_light_shade(light: DirectionalLight, normal: vec3<f32>, view: vec3<f32>, specular: SpecularAttributes) -> Lighting {
+    return base_light_shade(light.base, normal, -normalize(light.direction), view, specular);
+}
+
+fn spot_light_shade(light: SpotLight, normal: vec3<f32>, view: vec3<f32>, specular: SpecularAttributes, frag_position: vec3<f32>) -> Lighting {
+    let to_light = light.position - frag_position;
+    let direction = normalize(to_light);
+    let theta = dot(-direction, normalize(light.direction));
+    let cone = clamp((theta - light.outer_cutoff) / max(light.inner_cutoff - light.outer_cutoff, 0.0001), 0.0, 1.0);
+    let lighting = base_light_shade(light.base, normal, direction, view, specular);
+    return scale_lighting(lighting, attenuate(light.atten, length(to_light)) * cone);
+}
+
+fn add_lighting(a: Lighting, b: Lighting) -> Lighting {
+    return Lighting(a.diffuse + b.diffuse, a.specular + b.specular);
+}
 
 [[stage(fragment)]]
 fn fs(in: FragmentInput) -> [[location(0)]] vec4<f32> {
-    let albedo = textureSample(albedo_texture, texture_sampler, in.uv).rgb;
-    var normal = textureSample(normal_texture, normal_sampler, in.uv).rgb;
-    normal.g = 1.0 - normal.g;
-    normal = normalize(normal * 2.0 - 1.0);
-
-    var direction = vec3<f32>(point_light.position.xy, 1.0) - in.frag_position.xyz;
-    let distance = length(direction);
-    direction  = normalize(direction);
+    let albedo = textureSample(albedo_texture, texture_sampler, in.uv).rgb * material.tint;
+    let raw_normal = textureSample(normal_texture, normal_sampler, in.uv).rgb;
+    var flipped_normal = raw_normal;
+    flipped_normal.g = 1.0 - flipped_normal.g;
+    let world = tbn(in);
+    let normal = normalize(world * decode_normal(raw_normal));
+
+    let specular_sample = textureSample(specular_texture, texture_sampler, in.uv);
+    let specular = SpecularAttributes(
+        material.specular_power * specular_sample.g,
+        material.specular_intensity * specular_sample.r
+    );
+    let emissive = textureSample(emissive_texture, texture_sampler, in.uv).rgb * material.emissive;
+    // Orthographic, so every fragment sees the eye along the same direction.
+    let view = -normalize(eye.view_direction);
+
+    var lighting = Lighting(scene_ambient(scene_lights.ambient, normal), vec3<f32>(0.0));
+    let tile = light_tile(in.position.xy);
+    let count = min(tile_light_count(tile), light_grid.max_lights_per_tile);
+    for (var j = 0u; j < count; j = j + 1u) {
+        let i = tile_light_index(tile * light_grid.max_lights_per_tile + j);
+        if (i < MAX_POINT_LIGHTS) {
+            lighting = add_lighting(lighting, point_light_shade(point_lights.lights[i], normal, view, specular, in.frag_position));
+        }
+    }
+    for (var i = 0u; i < min(scene_lights.directional_count, MAX_DIRECTIONAL_LIGHTS); i = i + 1u) {
+        lighting = add_lighting(lighting, directional_light_shade(directional_lights.lights[i], normal, view, specular));
+    }
+    for (var i = 0u; i < min(scene_lights.spot_count, MAX_SPOT_LIGHTS); i = i + 1u) {
+        lighting = add_lighting(lighting, spot_light_shade(spot_lights.lights[i], normal, view, specular, in.frag_position));
+    }
+
+    // The debug views show the first light only, and straight on without one.
+    var direction = vec3<f32>(0.0, 0.0, 1.0);
+    if (scene_lights.count > 0u) {
+        direction = normalize(point_lights.lights[0].position - in.frag_position);
+    }
     let max_dot = max(dot(normal, direction), 0.0);
-    let diffuse = point_light.base.color * point_light.base.diffuse_intensity * max_dot;
-    let attenuation = 1.0 / ((point_light.atten.constant) + (point_light.atten.linear * distance) + (point_light.atten.exp * distance * distance));
 
-    return vec4<f32>(albedo * diffuse * attenuation, 1.0);
+    switch (debug.view) {
+        case 1u: { return vec4<f32>(raw_normal, 1.0); }
+        case 2u: { return vec4<f32>(flipped_normal, 1.0); }
+        case 3u: { return vec4<f32>(vec3<f32>(raw_normal.r), 1.0); }
+        case 4u: { return vec4<f32>(vec3<f32>(raw_normal.g), 1.0); }
+        case 5u: { return vec4<f32>(vec3<f32>(raw_normal.b), 1.0); }
+        case 6u: { return vec4<f32>(direction, 1.0); }
+        case 7u: { return vec4<f32>(vec3<f32>(max_dot), 1.0); }
+        case 8u: {
+            let original_y_normal = normalize(world * (raw_normal * 2.0 - 1.0));
+            return vec4<f32>(vec3<f32>(max(dot(original_y_normal, direction), 0.0)), 1.0);
+        }
+        default: { return vec4<f32>(albedo * lighting.diffuse + lighting.specular + emissive, 1.0); }
+    }
 }
\ No newline at end of file
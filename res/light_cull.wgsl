@@ -0,0 +1,142 @@
+struct View {
+    view_proj: mat4x4<f32>;
+};
+
+struct BaseLight
+{
+    color: vec3<f32>;
+    ambient_intensity: f32;
+    diffuse_intensity: f32;
+};
+
+struct Attenuation
+{
+    constant: f32;
+    linear: f32;
+    exp: f32;
+};
+
+// Laid out like the std140 GPUPointLight: std140 pads the vec3 position to
+// 16 bytes, so atten starts at offset 48.
+struct PointLight
+{
+    base: BaseLight;
+    position: vec3<f32>;
+    position_padding: f32;
+    atten: Attenuation;
+};
+
+// Replaced at pipeline creation with the cap derived from the device limits.
+let MAX_POINT_LIGHTS: u32 = 256u;
+
+struct PointLights
+{
+    lights: array<PointLight, MAX_POINT_LIGHTS>;
+};
+
+struct SceneLights
+{
+    count: u32;
+    directional_count: u32;
+    spot_count: u32;
+};
+
+struct LightGrid
+{
+    tiles: vec2<u32>;
+    tile_size: vec2<f32>;
+    target_size: vec2<f32>;
+    max_lights_per_tile: u32;
+    cutoff: f32;
+};
+
+struct TileLightCounts
+{
+    counts: array<u32>;
+};
+
+struct TileLightIndices
+{
+    indices: array<u32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+[[group(0), binding(1)]]
+var<uniform> point_lights: PointLights;
+
+[[group(0), binding(2)]]
+var<uniform> scene_lights: SceneLights;
+
+[[group(0), binding(3)]]
+var<uniform> light_grid: LightGrid;
+
+[[group(0), binding(4)]]
+var<storage, read_write> tile_light_counts: TileLightCounts;
+
+[[group(0), binding(5)]]
+var<storage, read_write> tile_light_indices: TileLightIndices;
+
+// Mirrors GPUAttenuation::radius; a negative result means the light never falls off.
+fn light_radius(light: PointLight) -> f32 {
+    let intensity = max(light.base.diffuse_intensity, light.base.ambient_intensity);
+    let brightest = max(light.base.color.r, max(light.base.color.g, light.base.color.b)) * intensity;
+    let reach = brightest / light_grid.cutoff - light.atten.constant;
+    if (reach <= 0.0) {
+        return 0.0;
+    }
+    if (light.atten.exp > 0.0) {
+        let linear = light.atten.linear;
+        return (-linear + sqrt(linear * linear + 4.0 * light.atten.exp * reach)) / (2.0 * light.atten.exp);
+    }
+    if (light.atten.linear > 0.0) {
+        return reach / light.atten.linear;
+    }
+    return -1.0;
+}
+
+fn to_pixels(world: vec3<f32>) -> vec2<f32> {
+    let clip = view.view_proj * vec4<f32>(world, 1.0);
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>((ndc.x * 0.5 + 0.5) * light_grid.target_size.x, (0.5 - ndc.y * 0.5) * light_grid.target_size.y);
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn cull([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= light_grid.tiles.x || id.y >= light_grid.tiles.y) {
+        return;
+    }
+
+    let tile = id.y * light_grid.tiles.x + id.x;
+    let tile_min = vec2<f32>(id.xy) * light_grid.tile_size;
+    let tile_max = tile_min + light_grid.tile_size;
+    let total = min(scene_lights.count, MAX_POINT_LIGHTS);
+
+    var count = 0u;
+    for (var i = 0u; i < total; i = i + 1u) {
+        if (count >= light_grid.max_lights_per_tile) {
+            break;
+        }
+
+        let light = point_lights.lights[i];
+        let radius = light_radius(light);
+        var overlaps = radius < 0.0;
+        if (!overlaps) {
+            let a = to_pixels(light.position + vec3<f32>(-radius, -radius, 0.0));
+            let b = to_pixels(light.position + vec3<f32>(radius, -radius, 0.0));
+            let c = to_pixels(light.position + vec3<f32>(radius, radius, 0.0));
+            let d = to_pixels(light.position + vec3<f32>(-radius, radius, 0.0));
+            let light_min = min(min(a, b), min(c, d));
+            let light_max = max(max(a, b), max(c, d));
+            overlaps = all(light_min < tile_max) && all(light_max >= tile_min);
+        }
+
+        if (overlaps) {
+            tile_light_indices.indices[tile * light_grid.max_lights_per_tile + count] = i;
+            count = count + 1u;
+        }
+    }
+
+    tile_light_counts.counts[tile] = count;
+}
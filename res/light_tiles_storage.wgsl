@@ -0,0 +1,24 @@
+// Per-tile light lists written by the light_cull compute pass.
+struct TileLightCounts
+{
+    counts: array<u32>;
+};
+
+struct TileLightIndices
+{
+    indices: array<u32>;
+};
+
+[[group(3), binding(1)]]
+var<storage, read> tile_light_counts: TileLightCounts;
+
+[[group(3), binding(2)]]
+var<storage, read> tile_light_indices: TileLightIndices;
+
+fn tile_light_count(tile: u32) -> u32 {
+    return tile_light_counts.counts[tile];
+}
+
+fn tile_light_index(slot: u32) -> u32 {
+    return tile_light_indices.indices[slot];
+}
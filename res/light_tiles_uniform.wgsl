@@ -0,0 +1,28 @@
+// Per-tile light lists binned on the CPU. Uniform arrays need a 16 byte
+// stride, so the u32 counts and indices are packed four to a vec4.
+let TILE_COUNT_VEC4: u32 = 36u;
+let TILE_INDEX_VEC4: u32 = 1008u;
+
+struct TileLightCounts
+{
+    counts: array<vec4<u32>, TILE_COUNT_VEC4>;
+};
+
+struct TileLightIndices
+{
+    indices: array<vec4<u32>, TILE_INDEX_VEC4>;
+};
+
+[[group(3), binding(1)]]
+var<uniform> tile_light_counts: TileLightCounts;
+
+[[group(3), binding(2)]]
+var<uniform> tile_light_indices: TileLightIndices;
+
+fn tile_light_count(tile: u32) -> u32 {
+    return tile_light_counts.counts[tile / 4u][tile % 4u];
+}
+
+fn tile_light_index(slot: u32) -> u32 {
+    return tile_light_indices.indices[slot / 4u][slot % 4u];
+}
@@ -0,0 +1,65 @@
+// Renders one mip level from the level above it. Each output texel averages
+// the 2x2 block of source texels it covers, clamped at odd edges.
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+};
+
+// A triangle covering the whole target.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+[[group(0), binding(0)]]
+var source: texture_2d<f32>;
+
+fn source_texel(pixel: vec2<i32>, offset: vec2<i32>) -> vec4<f32> {
+    let last = textureDimensions(source) - vec2<i32>(1, 1);
+    return textureLoad(source, min(pixel * 2 + offset, last), 0);
+}
+
+// sRGB views decode on load and encode on store, so color averages in linear space.
+[[stage(fragment)]]
+fn fs_color(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    return (source_texel(pixel, vec2<i32>(0, 0))
+        + source_texel(pixel, vec2<i32>(1, 0))
+        + source_texel(pixel, vec2<i32>(0, 1))
+        + source_texel(pixel, vec2<i32>(1, 1))) * 0.25;
+}
+
+fn decode_normal(texel: vec4<f32>) -> vec3<f32> {
+    return normalize(texel.xyz * 2.0 - 1.0);
+}
+
+fn decode_two_channel(texel: vec4<f32>) -> vec3<f32> {
+    let xy = texel.xy * 2.0 - 1.0;
+    return vec3<f32>(xy, sqrt(max(1.0 - dot(xy, xy), 0.0)));
+}
+
+// Averaging unit normals shortens them, so the sum is renormalized.
+[[stage(fragment)]]
+fn fs_normal(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let a = source_texel(pixel, vec2<i32>(0, 0));
+    let b = source_texel(pixel, vec2<i32>(1, 0));
+    let c = source_texel(pixel, vec2<i32>(0, 1));
+    let d = source_texel(pixel, vec2<i32>(1, 1));
+    let normal = normalize(decode_normal(a) + decode_normal(b) + decode_normal(c) + decode_normal(d));
+    return vec4<f32>(normal * 0.5 + 0.5, (a.w + b.w + c.w + d.w) * 0.25);
+}
+
+[[stage(fragment)]]
+fn fs_normal_two_channel(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let normal = normalize(
+        decode_two_channel(source_texel(pixel, vec2<i32>(0, 0)))
+        + decode_two_channel(source_texel(pixel, vec2<i32>(1, 0)))
+        + decode_two_channel(source_texel(pixel, vec2<i32>(0, 1)))
+        + decode_two_channel(source_texel(pixel, vec2<i32>(1, 1))));
+    return vec4<f32>(normal.xy * 0.5 + 0.5, 0.0, 1.0);
+}
@@ -0,0 +1,59 @@
+// Maps the HDR scene color into the displayable range of the target.
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+struct TonemapSettings {
+    exposure: f32;
+    tonemapper: u32;
+    bloom_intensity: f32;
+};
+
+[[group(0), binding(0)]]
+var hdr_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var bloom_texture: texture_2d<f32>;
+[[group(0), binding(2)]]
+var bloom_sampler: sampler;
+[[group(0), binding(3)]]
+var<uniform> settings: TonemapSettings;
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (vec3<f32>(1.0) + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let hdr = textureLoad(hdr_texture, vec2<i32>(in.position.xy), 0);
+    // Bloom is added before exposure and the curve, so it rolls off with the rest.
+    let bloom = textureSample(bloom_texture, bloom_sampler, in.uv).rgb * settings.bloom_intensity;
+    let color = max((hdr.rgb + bloom) * settings.exposure, vec3<f32>(0.0));
+    var mapped: vec3<f32>;
+    switch (settings.tonemapper) {
+        case 1u: { mapped = reinhard(color); }
+        case 2u: { mapped = aces(color); }
+        default: { mapped = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)); }
+    }
+    return vec4<f32>(mapped, hdr.a);
+}
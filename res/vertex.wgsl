@@ -4,28 +4,50 @@ struct View {
 [[group(0), binding(0)]]
 var<uniform> view: View;
 
-struct Mesh {
-    mesh: mat4x4<f32>;
-};
-[[group(2), binding(0)]]
-var<uniform> mesh: Mesh;
 
 struct VertexInput {
     [[location(0)]] position : vec3<f32>;
     [[location(1)]] uv : vec2<f32>;
+    [[location(2)]] normal : vec3<f32>;
+    [[location(3)]] tangent : vec4<f32>;
+};
+
+// One per sprite, see SpriteInstance.
+struct InstanceInput {
+    [[location(4)]] model_0: vec4<f32>;
+    [[location(5)]] model_1: vec4<f32>;
+    [[location(6)]] model_2: vec4<f32>;
+    [[location(7)]] model_3: vec4<f32>;
+    [[location(8)]] normal_0: vec3<f32>;
+    [[location(9)]] normal_1: vec3<f32>;
+    [[location(10)]] normal_2: vec3<f32>;
+    [[location(11)]] handedness: f32;
+    // Atlas region as (min u, min v, max u, max v).
+    [[location(12)]] uv_rect: vec4<f32>;
 };
 
 struct VertexOutput {
     [[builtin(position)]] position: vec4<f32>;
     [[location(0)]] frag_position: vec3<f32>;
     [[location(1)]] uv: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+    [[location(3)]] tangent: vec4<f32>;
 };
 
 [[stage(vertex)]]
-fn vs(in: VertexInput) -> VertexOutput {
+fn vs(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let mesh = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let normal_matrix = mat3x3<f32>(instance.normal_0, instance.normal_1, instance.normal_2);
+
     var out: VertexOutput;
-    out.position =  view.view_proj * mesh.mesh * vec4<f32>(in.position, 1.0);
-    out.frag_position = vec3<f32>(mesh.mesh * vec4<f32>(in.position, 1.0)).xyz;
-    out.uv = in.uv;
+    out.position =  view.view_proj * mesh * vec4<f32>(in.position, 1.0);
+    out.frag_position = vec3<f32>(mesh * vec4<f32>(in.position, 1.0)).xyz;
+    out.uv = mix(instance.uv_rect.xy, instance.uv_rect.zw, in.uv);
+
+    // Tangents follow the surface so they use the model matrix, normals use the
+    // inverse transpose. A mirrored transform flips the bitangent's handedness.
+    let model = mat3x3<f32>(mesh[0].xyz, mesh[1].xyz, mesh[2].xyz);
+    out.normal = normalize(normal_matrix * in.normal);
+    out.tangent = vec4<f32>(normalize(model * in.tangent.xyz), in.tangent.w * instance.handedness);
     return out;
-}
\ No newline at end of file
+}
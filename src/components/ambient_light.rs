@@ -0,0 +1,46 @@
+use crevice::std140::AsStd140;
+use glam::{Vec3, vec3};
+use serde::{Deserialize, Serialize};
+
+/// Scene-wide light that reaches every fragment regardless of the lights in
+/// the scene, so unlit parts of a sprite aren't black.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AmbientLight {
+    Flat { color: Vec3, intensity: f32 },
+    /// Blends from `ground` to `sky` by how far the mapped normal faces `up`.
+    Hemisphere { sky: Vec3, ground: Vec3, up: Vec3, intensity: f32 }
+}
+
+impl Default for AmbientLight {
+    fn default() -> Self {
+        AmbientLight::Flat { color: vec3(1.0, 1.0, 1.0), intensity: 0.0 }
+    }
+}
+
+/// A flat ambient light is uploaded as a hemisphere with matching colors.
+#[derive(AsStd140)]
+pub struct GPUAmbientLight {
+    pub sky_color: Vec3,
+    pub intensity: f32,
+    pub ground_color: Vec3,
+    pub up: Vec3
+}
+
+impl From<AmbientLight> for GPUAmbientLight {
+    fn from(ambient: AmbientLight) -> Self {
+        match ambient {
+            AmbientLight::Flat { color, intensity } => Self {
+                sky_color: color,
+                intensity,
+                ground_color: color,
+                up: Vec3::Y
+            },
+            AmbientLight::Hemisphere { sky, ground, up, intensity } => Self {
+                sky_color: sky,
+                intensity,
+                ground_color: ground,
+                up: up.normalize_or_zero()
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+/// Frames shorter than this are stretched to it, so a zero duration can't stall `update`.
+const MIN_FRAME_DURATION: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackMode {
+    #[default]
+    Loop,
+    /// Plays forwards then backwards, without repeating the end frames.
+    PingPong,
+    /// Stops on the last frame.
+    OneShot
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationFrame {
+    /// Atlas region shown for this frame. Albedo and normal atlases share a
+    /// layout, so both advance together.
+    pub region: String,
+    /// Seconds.
+    pub duration: f32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    pub frames: Vec<AnimationFrame>,
+    pub mode: PlaybackMode
+}
+
+impl AnimationClip {
+    /// A clip where every frame lasts `frame_duration` seconds.
+    pub fn uniform(name: &str, regions: &[&str], frame_duration: f32, mode: PlaybackMode) -> Self {
+        Self {
+            name: name.to_string(),
+            frames: regions.iter()
+                .map(|region| AnimationFrame { region: region.to_string(), duration: frame_duration })
+                .collect(),
+            mode
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnimationEvent {
+    /// A looping or ping-pong clip wrapped back to its first frame.
+    Looped { clip: String },
+    /// A one-shot clip reached its last frame and stopped.
+    Finished { clip: String }
+}
+
+/// Steps a sprite through the frames of its current clip.
+#[derive(Debug, Clone, Default)]
+pub struct SpriteAnimation {
+    pub clips: HashMap<String, AnimationClip>,
+    pub current: Option<String>,
+    pub frame: usize,
+    pub playing: bool,
+    elapsed: f32,
+    reversing: bool
+}
+
+impl SpriteAnimation {
+    pub fn new(clips: impl IntoIterator<Item = AnimationClip>) -> Self {
+        Self {
+            clips: clips.into_iter().map(|clip| (clip.name.clone(), clip)).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Starts `clip` from its first frame. Returns false if there is no such clip.
+    pub fn play(&mut self, clip: &str) -> bool {
+        if !self.clips.contains_key(clip) {
+            return false;
+        }
+        self.current = Some(clip.to_string());
+        self.frame = 0;
+        self.elapsed = 0.0;
+        self.reversing = false;
+        self.playing = true;
+        true
+    }
+
+    pub fn current_clip(&self) -> Option<&AnimationClip> {
+        self.current.as_ref().and_then(|name| self.clips.get(name))
+    }
+
+    /// The atlas region for the current frame.
+    pub fn current_region(&self) -> Option<&str> {
+        self.current_clip()
+            .and_then(|clip| clip.frames.get(self.frame))
+            .map(|frame| frame.region.as_str())
+    }
+
+    /// Advances by `dt` seconds, possibly across several frames, and returns
+    /// any clip end events in the order they happened.
+    pub fn update(&mut self, dt: f32) -> Vec<AnimationEvent> {
+        let mut events = Vec::new();
+        let clip = match self.current.as_ref().and_then(|name| self.clips.get(name)) {
+            Some(clip) if self.playing && !clip.frames.is_empty() => clip,
+            _ => return events
+        };
+
+        self.elapsed += dt;
+        let last = clip.frames.len() - 1;
+        loop {
+            let duration = clip.frames[self.frame].duration.max(MIN_FRAME_DURATION);
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+
+            match clip.mode {
+                PlaybackMode::Loop => {
+                    if self.frame == last {
+                        self.frame = 0;
+                        events.push(AnimationEvent::Looped { clip: clip.name.clone() });
+                    } else {
+                        self.frame += 1;
+                    }
+                },
+                PlaybackMode::PingPong => {
+                    if last == 0 {
+                        events.push(AnimationEvent::Looped { clip: clip.name.clone() });
+                    } else if self.reversing {
+                        self.frame -= 1;
+                        if self.frame == 0 {
+                            self.reversing = false;
+                            events.push(AnimationEvent::Looped { clip: clip.name.clone() });
+                        }
+                    } else {
+                        self.frame += 1;
+                        self.reversing = self.frame == last;
+                    }
+                },
+                PlaybackMode::OneShot => {
+                    if self.frame == last {
+                        self.playing = false;
+                        self.elapsed = 0.0;
+                        events.push(AnimationEvent::Finished { clip: clip.name.clone() });
+                        break;
+                    }
+                    self.frame += 1;
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(mode: PlaybackMode) -> SpriteAnimation {
+        let mut animation = SpriteAnimation::new([AnimationClip::uniform("walk", &["a", "b", "c"], 0.1, mode)]);
+        animation.play("walk");
+        animation
+    }
+
+    fn regions(animation: &mut SpriteAnimation, steps: usize) -> Vec<String> {
+        (0..steps).map(|_| {
+            animation.update(0.1);
+            animation.current_region().unwrap().to_string()
+        }).collect()
+    }
+
+    #[test]
+    fn loop_wraps_and_reports() {
+        let mut animation = animation(PlaybackMode::Loop);
+        assert_eq!(regions(&mut animation, 4), ["b", "c", "a", "b"]);
+        let mut animation = self::animation(PlaybackMode::Loop);
+        assert_eq!(animation.update(0.35), vec![AnimationEvent::Looped { clip: "walk".to_string() }]);
+        assert_eq!(animation.current_region(), Some("a"));
+    }
+
+    #[test]
+    fn ping_pong_reverses_without_repeating_ends() {
+        let mut animation = animation(PlaybackMode::PingPong);
+        assert_eq!(regions(&mut animation, 6), ["b", "c", "b", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn one_shot_stops_on_last_frame() {
+        let mut animation = animation(PlaybackMode::OneShot);
+        assert!(animation.update(0.25).is_empty());
+        assert_eq!(animation.update(0.1), vec![AnimationEvent::Finished { clip: "walk".to_string() }]);
+        assert!(!animation.playing);
+        assert_eq!(animation.current_region(), Some("c"));
+        assert!(animation.update(1.0).is_empty());
+    }
+}
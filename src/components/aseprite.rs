@@ -0,0 +1,255 @@
+use std::fmt;
+
+use image::DynamicImage;
+use serde::{Deserialize, Deserializer, de::{MapAccess, Visitor}};
+
+use crate::render::{AtlasBuilder, AtlasError};
+use super::{AnimationClip, AnimationFrame, PlaybackMode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct AsepriteRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AsepriteFrame {
+    #[serde(default)]
+    pub filename: String,
+    pub frame: AsepriteRect,
+    /// Milliseconds.
+    pub duration: u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AsepriteDirection {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    #[serde(default)]
+    pub direction: AsepriteDirection,
+    /// Play count as a string, absent or "0" for forever.
+    #[serde(default)]
+    pub repeat: Option<String>
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AsepriteMeta {
+    #[serde(default)]
+    frame_tags: Vec<AsepriteTag>
+}
+
+/// A sprite sheet exported by Aseprite with "Array" or "Hash" frames and
+/// frame tags. Each tag becomes an animation clip.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsepriteSheet {
+    #[serde(deserialize_with = "frames_in_order")]
+    pub frames: Vec<AsepriteFrame>,
+    #[serde(default)]
+    meta: AsepriteMeta
+}
+
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    Json(serde_json::Error),
+    Atlas(AtlasError),
+    /// A frame's rectangle lies outside the sheet image.
+    FrameOutOfBounds { index: usize },
+    /// A tag refers to frames the sheet doesn't have.
+    TagOutOfRange { tag: String }
+}
+
+impl fmt::Display for SpriteSheetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpriteSheetError::Json(error) => write!(f, "invalid sprite sheet json: {}", error),
+            SpriteSheetError::Atlas(error) => write!(f, "{}", error),
+            SpriteSheetError::FrameOutOfBounds { index } => write!(f, "sprite sheet frame {} lies outside the image", index),
+            SpriteSheetError::TagOutOfRange { tag } => write!(f, "sprite sheet tag {} refers to missing frames", tag)
+        }
+    }
+}
+
+impl std::error::Error for SpriteSheetError {}
+
+impl From<AtlasError> for SpriteSheetError {
+    fn from(error: AtlasError) -> Self {
+        SpriteSheetError::Atlas(error)
+    }
+}
+
+impl AsepriteSheet {
+    pub fn from_json(json: &str) -> Result<Self, SpriteSheetError> {
+        serde_json::from_str(json).map_err(SpriteSheetError::Json)
+    }
+
+    pub fn tags(&self) -> &[AsepriteTag] {
+        &self.meta.frame_tags
+    }
+
+    /// The atlas region name used for frame `index` of the sheet added under `prefix`.
+    pub fn region_name(prefix: &str, index: usize) -> String {
+        format!("{}/{}", prefix, index)
+    }
+
+    /// Crops every frame out of the sheet and adds it to the atlas. The normal
+    /// sheet, if any, must share the albedo sheet's layout.
+    pub fn add_to_atlas(&self, builder: &mut AtlasBuilder, prefix: &str, albedo: &DynamicImage, normal: Option<&DynamicImage>) -> Result<(), SpriteSheetError> {
+        for (index, frame) in self.frames.iter().enumerate() {
+            let rect = frame.frame;
+            let fits = |image: &DynamicImage| {
+                rect.x.checked_add(rect.w).is_some_and(|right| right <= image.width())
+                    && rect.y.checked_add(rect.h).is_some_and(|bottom| bottom <= image.height())
+            };
+            if !fits(albedo) || !normal.is_none_or(fits) {
+                return Err(SpriteSheetError::FrameOutOfBounds { index });
+            }
+
+            let albedo_frame = albedo.crop_imm(rect.x, rect.y, rect.w, rect.h);
+            let normal_frame = normal.map(|normal| normal.crop_imm(rect.x, rect.y, rect.w, rect.h));
+            builder.add(&Self::region_name(prefix, index), &albedo_frame, normal_frame.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// One clip per frame tag, or a single looping clip named `prefix` over
+    /// every frame when the sheet has no tags. A tag with `repeat` of 1 plays once.
+    pub fn clips(&self, prefix: &str) -> Result<Vec<AnimationClip>, SpriteSheetError> {
+        let frame = |index: usize| AnimationFrame {
+            region: Self::region_name(prefix, index),
+            duration: self.frames[index].duration as f32 / 1000.0
+        };
+
+        if self.meta.frame_tags.is_empty() {
+            return Ok(vec![AnimationClip {
+                name: prefix.to_string(),
+                frames: (0..self.frames.len()).map(frame).collect(),
+                mode: PlaybackMode::Loop
+            }]);
+        }
+
+        self.meta.frame_tags.iter().map(|tag| {
+            if tag.from > tag.to || tag.to >= self.frames.len() {
+                return Err(SpriteSheetError::TagOutOfRange { tag: tag.name.clone() });
+            }
+            let mut frames: Vec<AnimationFrame> = (tag.from..=tag.to).map(frame).collect();
+            if matches!(tag.direction, AsepriteDirection::Reverse | AsepriteDirection::PingpongReverse) {
+                frames.reverse();
+            }
+            let mode = match (tag.direction, tag.repeat.as_deref()) {
+                (_, Some("1")) => PlaybackMode::OneShot,
+                (AsepriteDirection::Pingpong | AsepriteDirection::PingpongReverse, _) => PlaybackMode::PingPong,
+                _ => PlaybackMode::Loop
+            };
+            Ok(AnimationClip { name: tag.name.clone(), frames, mode })
+        }).collect()
+    }
+}
+
+/// Accepts both export layouts. Hash frames keep their file order, which is
+/// the frame order, rather than being sorted by name.
+fn frames_in_order<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<AsepriteFrame>, D::Error> {
+    struct FramesVisitor;
+
+    impl<'de> Visitor<'de> for FramesVisitor {
+        type Value = Vec<AsepriteFrame>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array or map of frames")
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some(frame) = seq.next_element()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some((filename, mut frame)) = map.next_entry::<String, AsepriteFrame>()? {
+                frame.filename = filename;
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+    }
+
+    deserializer.deserialize_any(FramesVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH_SHEET: &str = r#"{
+        "frames": {
+            "walk 10.png": { "frame": { "x": 0, "y": 0, "w": 4, "h": 4 }, "duration": 100 },
+            "walk 2.png": { "frame": { "x": 4, "y": 0, "w": 4, "h": 4 }, "duration": 250 },
+            "walk 0.png": { "frame": { "x": 8, "y": 0, "w": 4, "h": 4 }, "duration": 50 }
+        },
+        "meta": {
+            "frameTags": [
+                { "name": "bounce", "from": 0, "to": 2, "direction": "pingpong" },
+                { "name": "back", "from": 1, "to": 2, "direction": "reverse", "repeat": "1" }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn hash_frames_keep_file_order() {
+        let sheet = AsepriteSheet::from_json(HASH_SHEET).unwrap();
+        let names: Vec<&str> = sheet.frames.iter().map(|frame| frame.filename.as_str()).collect();
+        assert_eq!(names, ["walk 10.png", "walk 2.png", "walk 0.png"]);
+        assert_eq!(sheet.frames[1].frame, AsepriteRect { x: 4, y: 0, w: 4, h: 4 });
+    }
+
+    #[test]
+    fn tags_become_clips() {
+        let clips = AsepriteSheet::from_json(HASH_SHEET).unwrap().clips("hero").unwrap();
+        assert_eq!(clips[0].mode, PlaybackMode::PingPong);
+        assert_eq!(clips[0].frames[1], AnimationFrame { region: "hero/1".to_string(), duration: 0.25 });
+
+        assert_eq!(clips[1].mode, PlaybackMode::OneShot);
+        let regions: Vec<&str> = clips[1].frames.iter().map(|frame| frame.region.as_str()).collect();
+        assert_eq!(regions, ["hero/2", "hero/1"]);
+    }
+
+    #[test]
+    fn array_frames_without_tags_loop() {
+        let json = r#"{ "frames": [
+            { "filename": "a", "frame": { "x": 0, "y": 0, "w": 2, "h": 2 }, "duration": 100 },
+            { "filename": "b", "frame": { "x": 2, "y": 0, "w": 2, "h": 2 }, "duration": 100 }
+        ] }"#;
+        let clips = AsepriteSheet::from_json(json).unwrap().clips("coin").unwrap();
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].name, "coin");
+        assert_eq!(clips[0].frames.len(), 2);
+        assert_eq!(clips[0].mode, PlaybackMode::Loop);
+    }
+
+    #[test]
+    fn overflowing_frames_are_out_of_bounds() {
+        let json = r#"{ "frames": [
+            { "filename": "a", "frame": { "x": 4294967295, "y": 0, "w": 2, "h": 2 }, "duration": 100 }
+        ] }"#;
+        let sheet = AsepriteSheet::from_json(json).unwrap();
+        let image = DynamicImage::new_rgba8(4, 4);
+        let result = sheet.add_to_atlas(&mut AtlasBuilder::new(0), "coin", &image, None);
+        assert!(matches!(result, Err(SpriteSheetError::FrameOutOfBounds { index: 0 })));
+    }
+}
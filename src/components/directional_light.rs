@@ -0,0 +1,33 @@
+use crevice::std140::AsStd140;
+use glam::{Vec3, vec3};
+
+use super::{transform::Transform, GPUBaseLight};
+
+/// A light infinitely far away, shining along `direction` with no falloff.
+#[derive(AsStd140)]
+pub struct GPUDirectionalLight {
+    pub base_light: GPUBaseLight,
+    pub direction: Vec3
+}
+
+impl Default for GPUDirectionalLight {
+    fn default() -> Self {
+        Self {
+            base_light: GPUBaseLight::default(),
+            direction: vec3(0.0, 0.0, -1.0)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DirectionalLight {
+    pub transform: Transform,
+    pub gpu_light: GPUDirectionalLight
+}
+
+impl DirectionalLight {
+    /// Shines along the transform's local -Z, so an unrotated light points into the screen.
+    pub fn sync_direction(&mut self) {
+        self.gpu_light.direction = self.transform.rotation * -Vec3::Z;
+    }
+}
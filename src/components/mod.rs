@@ -1,9 +1,19 @@
 pub mod transform;
 pub mod quad;
 pub mod point_light;
+pub mod directional_light;
+pub mod spot_light;
+pub mod ambient_light;
 pub mod sprite;
+pub mod animation;
+pub mod aseprite;
 
 pub use transform::*;
 pub use quad::*;
 pub use point_light::*;
-pub use sprite::*;
\ No newline at end of file
+pub use directional_light::*;
+pub use spot_light::*;
+pub use ambient_light::*;
+pub use sprite::*;
+pub use animation::*;
+pub use aseprite::*;
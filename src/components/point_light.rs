@@ -1,12 +1,12 @@
 use crevice::std140::{AsStd140, Std140};
 use glam::*;
 use glam::{Vec3};
-use wgpu::{Buffer, Device, util::{BufferInitDescriptor, DeviceExt}, BufferUsages, BindGroupLayout, BindGroupLayoutDescriptor, ShaderStages, BindingType, BindGroupLayoutEntry, BindGroup, BufferBindingType, BufferSize};
+use serde::{Deserialize, Serialize};
 
+use super::{transform::Transform, Quad, GPUAmbientLight};
 
-use super::{transform::Transform, Quad};
-
-#[derive(AsStd140)]
+#[derive(AsStd140, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GPUBaseLight {
     pub color: glam::Vec3,
     pub ambient_intensity: f32,
@@ -23,13 +23,31 @@ impl Default for GPUBaseLight {
     }
 }
 
-#[derive(AsStd140)]
+#[derive(AsStd140, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GPUAttenuation {
     pub constant: f32,
     pub linear: f32,
     pub exp: f32,
 }
 
+impl GPUAttenuation {
+    /// Distance at which `intensity / attenuation` falls to `cutoff`, matching
+    /// the falloff in `frag.wgsl`. Lights that never fall off return `f32::INFINITY`.
+    pub fn radius(&self, intensity: f32, cutoff: f32) -> f32 {
+        let target = intensity / cutoff - self.constant;
+        if target <= 0.0 {
+            0.0
+        } else if self.exp > 0.0 {
+            (-self.linear + (self.linear * self.linear + 4.0 * self.exp * target).sqrt()) / (2.0 * self.exp)
+        } else if self.linear > 0.0 {
+            target / self.linear
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
 impl Default for GPUAttenuation {
     fn default() -> GPUAttenuation {
         GPUAttenuation {
@@ -40,14 +58,12 @@ impl Default for GPUAttenuation {
     }
 }
 
+/// With an orthographic camera every view ray is parallel, so specular
+/// highlights use `view_direction` rather than the direction to `eye_position`.
 #[derive(AsStd140)]
 pub struct EyePosition {
-    pub eye_position: Vec3
-}
-
-#[derive(AsStd140)]
-pub struct ScreenSize {
-    pub screen_size: Vec2
+    pub eye_position: Vec3,
+    pub view_direction: Vec3
 }
 
 #[derive(AsStd140)]
@@ -68,31 +84,45 @@ impl Default for GPUPointLight {
 }
 
 impl GPUPointLight {
-    pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
-        device.create_bind_group_layout(
-        &BindGroupLayoutDescriptor {
-                entries: &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Buffer {
-                            ty: BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: BufferSize::new(Self::std140_size_static() as u64)
-                        },
-                        count: None
-                    }
-                ],
-                label: Some("point_light_bind_group_layout")
-            }
-        )
+    pub fn radius(&self, cutoff: f32) -> f32 {
+        let base = &self.base_light;
+        let brightest = base.color.max_element() * base.diffuse_intensity.max(base.ambient_intensity);
+        self.atten.radius(brightest, cutoff)
+    }
+
+    /// Largest light array that fits in one uniform binding, capped at `requested`.
+    /// `downlevel_webgl2_defaults` allows 16 KiB per binding, or 256 lights.
+    pub fn max_lights(limits: &wgpu::Limits, requested: u32) -> u32 {
+        let per_binding = limits.max_uniform_buffer_binding_size / Self::std140_size_static() as u32;
+        requested.min(per_binding).max(1)
     }
 }
 
+/// Packs lights back to back; std140 struct sizes are a multiple of 16 so
+/// they match the WGSL array stride.
+pub fn pack_lights<'a, T: AsStd140 + 'a>(lights: impl Iterator<Item = &'a T>, max_lights: u32) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(max_lights as usize * T::std140_size_static());
+    for light in lights.take(max_lights as usize) {
+        bytes.extend_from_slice(light.as_std140().as_bytes());
+    }
+    bytes
+}
+
+/// Light counts and the scene ambient. `count` is the number of point lights.
+#[derive(AsStd140)]
+pub struct GPUSceneLights {
+    pub count: u32,
+    pub directional_count: u32,
+    pub spot_count: u32,
+    pub ambient: GPUAmbientLight
+}
+
+/// Blinn-Phong parameters for a material. The material's specular map scales
+/// `intensity` by its red channel and `power` by its green (gloss) channel.
 #[derive(AsStd140)]
 pub struct GPUSpecularAttributes {
-    power: f32,
-    intensity: f32
+    pub power: f32,
+    pub intensity: f32
 }
 
 impl Default for GPUSpecularAttributes {
@@ -104,56 +134,16 @@ impl Default for GPUSpecularAttributes {
     }
 }
 
-impl GPUSpecularAttributes {
-    pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
-        device.create_bind_group_layout(
-        &BindGroupLayoutDescriptor {
-                entries: &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Buffer {
-                            ty: BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: BufferSize::new(Self::std140_size_static() as u64)
-                        },
-                        count: None
-                    }
-                ],
-                label: Some("specular_attributes_group_layout")
-            }
-        )
-    }
-}
-
+#[derive(Default)]
 pub struct PointLight {
     pub transform: Transform,
     pub mesh: Quad,
-    pub gpu_light: GPUPointLight,
-    pub light_buffer: Option<Buffer>,
-    pub bind_group: Option<BindGroup>,
-}
-
-impl Default for PointLight {
-    fn default() -> Self {
-        Self {
-            transform: Transform::default(),
-            mesh: Quad::default(),
-            gpu_light: GPUPointLight::default(),
-            light_buffer: None,
-            bind_group: None
-        }
-    }
+    pub gpu_light: GPUPointLight
 }
 
 impl PointLight {
-    pub fn build_buffers(&mut self, device: &Device) {
-
-        let light_buffer = Some(device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("light_buffer"),
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-            contents: self.gpu_light.as_std140().as_bytes()
-        }));
-        self.light_buffer = light_buffer;
+    /// Copies the transform's translation into the GPU light before upload.
+    pub fn sync_position(&mut self) {
+        self.gpu_light.position = self.transform.translation;
     }
 }
\ No newline at end of file
@@ -1,21 +1,20 @@
 use std::mem::size_of;
 
-use glam::{Vec2, vec2, UVec2, uvec2};
-use wgpu::{Buffer, Device, util::{BufferInitDescriptor, DeviceExt}, BufferUsages, BufferDescriptor, ShaderModule, BindGroupLayout, BindGroupLayoutDescriptor, ShaderStages, BindingType, TextureSampleType, BindGroupLayoutEntry, SamplerBindingType, BindGroup, BindGroupDescriptor, BindingResource, BindGroupEntry, BufferBindingType, BufferSize};
+use glam::{UVec2, uvec2};
+use wgpu::{Buffer, Device, util::{BufferInitDescriptor, DeviceExt}, BufferUsages};
 
-use super::transform::Transform;
 
 pub struct Quad {
     pub size: UVec2,
     pub vertices: [f32; 12],
 
     pub vertex_buffer: Option<Buffer>,
-    pub index_buffer: Option<Buffer>,
-    pub mesh_buffer: Option<Buffer>,
+    pub index_buffer: Option<Buffer>
 }
 
 impl Quad {
-    pub const VERTEX_SIZE: u64 = size_of::<[f32;20]>() as u64;
+    pub const FLOATS_PER_VERTEX: usize = 12;
+    pub const VERTEX_SIZE: u64 = size_of::<[f32; 48]>() as u64;
     pub const INDEX_SIZE: u64 = size_of::<[u16; 6]>() as u64;
 
     pub const INDICES: [u16; 6] = [
@@ -30,15 +29,26 @@ impl Quad {
         0.0, 0.0
     ];
 
-    pub fn get_vertex_data(&self) -> [f32; 20] {
-        let mut data: [f32; 20] = [0.0; 20];
+    /// Sprites face the camera, which looks down -Z.
+    pub const NORMAL: [f32; 3] = [0.0, 0.0, 1.0];
+
+    /// U increases along +X and V decreases along +Y, so the bitangent
+    /// (towards the top of the texture) is +Y with a positive handedness.
+    pub const TANGENT: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    pub fn get_vertex_data(&self) -> [f32; 48] {
+        let mut data: [f32; 48] = [0.0; 48];
         for i in 0..4 {
-            let mut data_offset = i * 5;
+            let mut data_offset = i * Self::FLOATS_PER_VERTEX;
             let vert_offset = i * 3;
             let uv_offset = i * 2;
             data[data_offset..(data_offset + 3)].clone_from_slice(&self.vertices[vert_offset..(vert_offset + 3)]);
             data_offset += 3;
             data[data_offset..(data_offset + 2)].clone_from_slice(&Self::UVS[uv_offset..(uv_offset + 2)]);
+            data_offset += 2;
+            data[data_offset..(data_offset + 3)].clone_from_slice(&Self::NORMAL);
+            data_offset += 3;
+            data[data_offset..(data_offset + 4)].clone_from_slice(&Self::TANGENT);
         }
 
         data
@@ -49,16 +59,7 @@ impl Quad {
     }
 
     pub fn build_buffers(&mut self, device: &Device) {
-        let mut data: [f32; 20] = [0.0; 20];
-        for i in 0..4 {
-            let mut data_offset = i * 5;
-            let vert_offset = i * 3;
-            let uv_offset = i * 2;
-            data[data_offset..(data_offset + 3)].clone_from_slice(&self.vertices[vert_offset..(vert_offset + 3)]);
-            data_offset += 3;
-            data[data_offset..(data_offset + 2)].clone_from_slice(&Self::UVS[uv_offset..(uv_offset + 2)]);
-        }
-
+        let data = self.get_vertex_data();
         let data = bytemuck::bytes_of(&data);
         self.vertex_buffer = Some(device.create_buffer_init(
             &BufferInitDescriptor {
@@ -93,8 +94,7 @@ impl From<UVec2> for Quad {
             size,
             vertices,
             vertex_buffer: None,
-            index_buffer: None,
-            mesh_buffer: None,
+            index_buffer: None
         }
     }
 }
@@ -0,0 +1,61 @@
+use crevice::std140::AsStd140;
+use glam::{Vec3, vec3};
+
+use super::{transform::Transform, GPUAttenuation, GPUBaseLight};
+
+/// A point light limited to a cone. Fragments inside `inner_cutoff` are fully
+/// lit and the light fades out towards `outer_cutoff`; both are cosines of the
+/// angle from `direction`.
+#[derive(AsStd140)]
+pub struct GPUSpotLight {
+    pub base_light: GPUBaseLight,
+    pub position: Vec3,
+    pub atten: GPUAttenuation,
+    pub direction: Vec3,
+    pub inner_cutoff: f32,
+    pub outer_cutoff: f32
+}
+
+impl Default for GPUSpotLight {
+    fn default() -> Self {
+        Self {
+            base_light: GPUBaseLight::default(),
+            position: vec3(0.0, 0.0, 0.0),
+            atten: GPUAttenuation::default(),
+            direction: vec3(0.0, 0.0, -1.0),
+            inner_cutoff: 1.0,
+            outer_cutoff: 1.0
+        }
+    }
+}
+
+pub struct SpotLight {
+    pub transform: Transform,
+    pub gpu_light: GPUSpotLight,
+    /// Half angle of the fully lit cone, in radians.
+    pub inner_angle: f32,
+    /// Half angle at which the light has faded out, in radians.
+    pub outer_angle: f32
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            gpu_light: GPUSpotLight::default(),
+            inner_angle: 20f32.to_radians(),
+            outer_angle: 30f32.to_radians()
+        }
+    }
+}
+
+impl SpotLight {
+    /// Copies the transform and cone angles into the GPU light before upload.
+    /// The cone points along the transform's local -Z.
+    pub fn sync(&mut self) {
+        self.gpu_light.position = self.transform.translation;
+        self.gpu_light.direction = self.transform.rotation * -Vec3::Z;
+        self.gpu_light.inner_cutoff = self.inner_angle.min(self.outer_angle).cos();
+        self.gpu_light.outer_cutoff = self.outer_angle.cos();
+    }
+}
@@ -1,63 +1,61 @@
-use std::{collections::HashMap};
-use wgpu::{Device,BindGroup, BindGroupDescriptor, BindingResource, BindGroupEntry};
-use crate::render::texture::Texture;
-use super::{transform::Transform, Quad};
+use glam::Vec2;
+use crate::render::{AtlasRegion, MaterialHandle, TextureAtlas};
+use super::{transform::Transform, Quad, SpriteAnimation, AnimationEvent};
 
+#[derive(Default)]
 pub struct Sprite {
     pub transform: Transform,
+    /// Sized to the sprite's texture; the batch renderer scales a shared unit quad by `mesh.size`.
     pub mesh: Quad,
-    pub bind_group: Option<BindGroup>,
+    pub material: MaterialHandle,
+    /// The part of the material's textures to draw, or all of it when `None`.
+    pub region: Option<AtlasRegion>,
+    /// Drives `region` through atlas frames when set.
+    pub animation: Option<SpriteAnimation>
 }
 
 impl Sprite {
-    pub fn build_buffers(&mut self, device: &Device, textures: &HashMap<&str, Texture>) {
-        // let mut data: [f32; 20] = [0.0; 20];
-        // for i in 0..4 {
-        //     let mut data_offset = i * 5;
-        //     let vert_offset = i * 3;
-        //     let uv_offset = i * 2;
-        //     data[data_offset..(data_offset + 3)].clone_from_slice(&self.mesh.vertices[vert_offset..(vert_offset + 3)]);
-        //     data_offset += 3;
-        //     data[data_offset..(data_offset + 2)].clone_from_slice(&Quad::UVS[uv_offset..(uv_offset + 2)]);
-        // }
+    /// A sprite showing one named region of an atlas, sized to match it.
+    pub fn from_region(atlas: &TextureAtlas, name: &str) -> Option<Self> {
+        let mut sprite = Self::default();
+        sprite.set_region(atlas, name).then_some(sprite)
+    }
 
-        // let layout = sprite_manager.bind_group_layouts.get(self.texture_name.as_str()).unwrap();
+    /// Points the sprite at a named atlas region and resizes its quad to the
+    /// region. Returns false and leaves the sprite unchanged if there is no such region.
+    pub fn set_region(&mut self, atlas: &TextureAtlas, name: &str) -> bool {
+        match atlas.region(name) {
+            Some(region) => {
+                self.mesh = Quad::from(region.size);
+                self.region = Some(region.clone());
+                true
+            },
+            None => false
+        }
+    }
 
-        // let texture = textures.get(self.texture_name.as_str()).unwrap();
-        // let normal_texture = textures.get(format!("{}_normal",self.texture_name).as_str()).unwrap();
-        // self.bind_group = Some(device.create_bind_group(
-        //     &BindGroupDescriptor {
-        //         layout: layout,
-        //         entries: &[
-        //             BindGroupEntry {
-        //                 binding: 0,
-        //                 resource: BindingResource::TextureView(&texture.view)
-        //             },
-        //             BindGroupEntry {
-        //                 binding: 1,
-        //                 resource: BindingResource::TextureView(&normal_texture.view)
-        //             },
-        //             BindGroupEntry {
-        //                 binding: 2,
-        //                 resource: BindingResource::Sampler(&texture.sampler)
-        //             },
-        //             BindGroupEntry {
-        //                 binding: 3,
-        //                 resource: BindingResource::Sampler(&normal_texture.sampler)
-        //             }
-        //         ],
-        //         label: Some("sprite_texture_bind_group")
-        //     }
-        // ));
+    /// Advances the animation by `dt` seconds and switches to the region of
+    /// the frame it lands on, returning any clip end events.
+    pub fn animate(&mut self, atlas: &TextureAtlas, dt: f32) -> Vec<AnimationEvent> {
+        let animation = match self.animation.as_mut() {
+            Some(animation) => animation,
+            None => return Vec::new()
+        };
+        let events = animation.update(dt);
+        if let Some(name) = animation.current_region() {
+            if self.region.as_ref().map(|region| region.name.as_str()) != Some(name) {
+                let name = name.to_string();
+                self.set_region(atlas, &name);
+            }
+        }
+        events
     }
-}
 
-impl Default for Sprite {
-    fn default() -> Self {
-        Self {
-            transform: Transform::default(),
-            mesh: Quad::default(),
-            bind_group: None,
+    /// Top left and bottom right UVs of the region drawn.
+    pub fn uv_rect(&self) -> (Vec2, Vec2) {
+        match self.region.as_ref() {
+            Some(region) => (region.uv_min, region.uv_max),
+            None => (Vec2::ZERO, Vec2::ONE)
         }
     }
-}
\ No newline at end of file
+}
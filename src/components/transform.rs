@@ -1,12 +1,31 @@
-use glam::{Vec3, Quat, Mat4};
-use wgpu::{Buffer, Device, util::{BufferInitDescriptor, DeviceExt}, BufferUsages, BufferDescriptor, ShaderModule, BindGroupLayout, BindGroupLayoutDescriptor, ShaderStages, BindingType, TextureSampleType, BindGroupLayoutEntry, SamplerBindingType, BindGroup, BindGroupDescriptor, BindingResource, BindGroupEntry, BufferBindingType, BufferSize};
+use crevice::std140::AsStd140;
+use glam::{Vec3, Quat, Mat3, Mat4};
+
+/// Per-object uniform. The normal matrix is the inverse transpose of the
+/// model's upper 3x3 so non-uniform scale keeps normals perpendicular, and
+/// `handedness` is -1 when the transform mirrors the sprite.
+#[derive(AsStd140)]
+pub struct GPUTransform {
+    pub model: Mat4,
+    pub normal_matrix: Mat3,
+    pub handedness: f32
+}
+
+impl GPUTransform {
+    pub fn from_matrix(model: Mat4) -> Self {
+        let linear = Mat3::from_mat4(model);
+        Self {
+            model,
+            normal_matrix: linear.inverse().transpose(),
+            handedness: linear.determinant().signum()
+        }
+    }
+}
 
 pub struct Transform {
     pub translation: Vec3,
     pub rotation: Quat,
-    pub scale: Vec3,
-    pub transform_buffer: Option<Buffer>,
-    pub bind_group: Option<BindGroup>
+    pub scale: Vec3
 }
 
 impl Transform {
@@ -25,9 +44,7 @@ impl Transform {
         Self {
             translation: Vec3::ZERO,
             rotation: Quat::IDENTITY,
-            scale: Vec3::ONE,
-            transform_buffer: None,
-            bind_group: None
+            scale: Vec3::ONE
         }
     }
 
@@ -35,44 +52,12 @@ impl Transform {
         Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
     }
 
-    pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
-        device.create_bind_group_layout(
-        &wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::VERTEX,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None
-                    },
-                    count: None
-                }
-            ],
-            label: Some("transform_bind_group_layout")
-        })
+    pub fn get_normal_matrix(&self) -> Mat3 {
+        Mat3::from_mat4(self.get_matrix()).inverse().transpose()
     }
 
-    pub fn build_buffers(&mut self, device: &Device, layout: &BindGroupLayout) {
-        self.transform_buffer = Some(device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("mesh_buffer"),
-            contents: bytemuck::cast_slice(&[self.get_matrix()]),
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
-        }));
-
-        self.bind_group = Some(device.create_bind_group(
-            &BindGroupDescriptor {
-                layout: layout,
-                entries: &[
-                    BindGroupEntry {
-                        binding: 0,
-                        resource: self.transform_buffer.as_ref().unwrap().as_entire_binding()
-                    },
-                ],
-                label: Some("mesh_bind_group")
-            }
-        ));
+    pub fn to_gpu(&self) -> GPUTransform {
+        GPUTransform::from_matrix(self.get_matrix())
     }
 }
 
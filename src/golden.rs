@@ -0,0 +1,419 @@
+use std::path::{Path, PathBuf};
+
+use glam::{Quat, vec3};
+use image::{Rgba, RgbaImage};
+use winit::dpi::PhysicalSize;
+
+use crate::{components::{PointLight, Transform, GPUPointLight, GPUBaseLight, GPUAttenuation, DirectionalLight, GPUDirectionalLight, SpotLight, GPUSpotLight, GPUSpecularAttributes, AmbientLight}, headless, render::DebugView, renderer::Renderer, scene::{default_specular_attributes, Scene}};
+
+/// Golden images are rendered at the same aspect ratio as the window so the
+/// camera bounds match what is seen on screen.
+pub const GOLDEN_SIZE: PhysicalSize<u32> = PhysicalSize::new(320, 180);
+
+/// When set, every checked scene overwrites its stored golden instead of comparing.
+pub const BLESS_ENV: &str = "BLESS_GOLDENS";
+
+pub fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// The debug view goldens are the images shown in the README.
+pub fn reference_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("debug-images")
+}
+
+pub fn diff_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("golden-diff")
+}
+
+pub struct GoldenScene {
+    pub name: &'static str,
+    pub view: DebugView,
+    /// Applied to the demo scene before rendering.
+    pub setup: fn(&mut Renderer, &mut Scene)
+}
+
+impl GoldenScene {
+    /// Where the blessed image for this scene is stored.
+    pub fn golden_path(&self) -> PathBuf {
+        let dir = if DebugView::ALL.iter().any(|view| view.name() == self.name) { reference_dir() } else { golden_dir() };
+        dir.join(format!("{}.png", self.name))
+    }
+}
+
+fn sprite_transform(scene: &mut Scene) -> &mut Transform {
+    &mut scene.sprites[0].transform
+}
+
+/// One scene per fragment shader debug output, blessed into `debug-images/`, plus transformed sprites that must light the same way.
+pub fn scenes() -> Vec<GoldenScene> {
+    let mut scenes: Vec<GoldenScene> = DebugView::ALL.iter()
+        .map(|view| GoldenScene { name: view.name(), view: *view, setup: |_, _| {} })
+        .collect();
+
+    scenes.push(GoldenScene {
+        name: "rotated_sprite",
+        view: DebugView::Lit,
+        setup: |_, scene| sprite_transform(scene).rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_3)
+    });
+    scenes.push(GoldenScene {
+        name: "flipped_sprite",
+        view: DebugView::Lit,
+        setup: |_, scene| sprite_transform(scene).scale *= vec3(-1.0, 1.0, 1.0)
+    });
+    scenes.push(GoldenScene {
+        name: "stretched_sprite",
+        view: DebugView::Lit,
+        setup: |_, scene| sprite_transform(scene).scale *= vec3(2.0, 0.5, 1.0)
+    });
+    scenes.push(GoldenScene {
+        name: "multiple_lights",
+        view: DebugView::Lit,
+        setup: |_, scene| {
+            for (x, color) in [(-15.0, vec3(1.0, 0.2, 0.2)), (15.0, vec3(0.2, 0.2, 1.0))] {
+                scene.lights.push(PointLight {
+                    transform: Transform::from_xyz(x, 5.0, 1.0),
+                    gpu_light: GPUPointLight {
+                        base_light: GPUBaseLight { color, diffuse_intensity: 50.0, ..Default::default() },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            }
+        }
+    });
+    scenes.push(GoldenScene {
+        name: "tiled_lights",
+        view: DebugView::Lit,
+        setup: |_, scene| {
+            scene.lights.clear();
+            for i in 0..48 {
+                let x = (i % 8) as f32 * 10.0 - 35.0;
+                let y = (i / 8) as f32 * 6.0 - 15.0;
+                let color = vec3((i % 3) as f32 * 0.5, ((i + 1) % 3) as f32 * 0.5, ((i + 2) % 3) as f32 * 0.5);
+                scene.lights.push(PointLight {
+                    transform: Transform::from_xyz(x, y, 1.0),
+                    gpu_light: GPUPointLight {
+                        base_light: GPUBaseLight { color, diffuse_intensity: 5.0, ..Default::default() },
+                        atten: GPUAttenuation { constant: 1.0, linear: 0.0, exp: 4.0 },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            }
+        }
+    });
+    scenes.push(GoldenScene {
+        name: "directional_light",
+        view: DebugView::Lit,
+        setup: |_, scene| {
+            scene.lights.clear();
+            scene.directional_lights.push(DirectionalLight {
+                transform: Transform {
+                    rotation: Quat::from_rotation_x(0.6) * Quat::from_rotation_y(-0.6),
+                    ..Default::default()
+                },
+                gpu_light: GPUDirectionalLight {
+                    base_light: GPUBaseLight { diffuse_intensity: 1.0, ..Default::default() },
+                    ..Default::default()
+                }
+            });
+        }
+    });
+    scenes.push(GoldenScene {
+        name: "spot_light",
+        view: DebugView::Lit,
+        setup: |_, scene| {
+            scene.lights.clear();
+            scene.spot_lights.push(SpotLight {
+                transform: Transform {
+                    translation: vec3(-10.0, 0.0, 10.0),
+                    rotation: Quat::from_rotation_y(-0.5),
+                    ..Default::default()
+                },
+                gpu_light: GPUSpotLight {
+                    base_light: GPUBaseLight { diffuse_intensity: 50.0, ..Default::default() },
+                    atten: GPUAttenuation { constant: 1.0, linear: 0.05, exp: 0.0 },
+                    ..Default::default()
+                },
+                inner_angle: 10f32.to_radians(),
+                outer_angle: 20f32.to_radians()
+            });
+        }
+    });
+    scenes.push(GoldenScene {
+        name: "glossy_specular",
+        view: DebugView::Lit,
+        setup: |renderer, scene| renderer.materials.get_mut(scene.atlas_material).unwrap().specular_attributes = GPUSpecularAttributes { power: 8.0, intensity: 1.0 }
+    });
+    scenes.push(GoldenScene {
+        name: "light_ambient",
+        view: DebugView::Lit,
+        setup: |_, scene| scene.lights[0].gpu_light.base_light.ambient_intensity = 5.0
+    });
+    scenes.push(GoldenScene {
+        name: "hemisphere_ambient",
+        view: DebugView::Lit,
+        setup: |_, scene| {
+            scene.lights.clear();
+            scene.ambient = AmbientLight::Hemisphere {
+                sky: vec3(0.4, 0.5, 0.9),
+                ground: vec3(0.15, 0.1, 0.05),
+                up: vec3(0.0, 1.0, 0.0),
+                intensity: 1.0
+            };
+        }
+    });
+    scenes.push(GoldenScene {
+        name: "sprite_batch",
+        view: DebugView::Lit,
+        setup: |_, scene| scene.fill_sprite_grid(100)
+    });
+    scenes
+}
+
+pub struct Tolerance {
+    /// Largest per-channel difference that still counts as a matching pixel.
+    pub channel: u8,
+    /// Fraction of pixels allowed to exceed `channel`.
+    pub max_mismatch_ratio: f32,
+    /// Lowest accepted mean structural similarity.
+    pub min_ssim: f32
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            max_mismatch_ratio: 0.001,
+            min_ssim: 0.98
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Comparison {
+    pub mismatched: usize,
+    pub total: usize,
+    pub max_delta: u8,
+    pub ssim: f32
+}
+
+impl Comparison {
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        let ratio = self.mismatched as f32 / self.total.max(1) as f32;
+        ratio <= tolerance.max_mismatch_ratio && self.ssim >= tolerance.min_ssim
+    }
+}
+
+fn channel_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter().zip(b.0.iter())
+        .map(|(x, y)| x.abs_diff(*y))
+        .max()
+        .unwrap_or(0)
+}
+
+fn luma(pixel: &Rgba<u8>) -> f32 {
+    0.2126 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[2] as f32
+}
+
+/// Mean SSIM of the luma channel over 8x8 windows.
+pub fn ssim(expected: &RgbaImage, actual: &RgbaImage) -> f32 {
+    const WINDOW: u32 = 8;
+    const C1: f32 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f32 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for wy in (0..expected.height()).step_by(WINDOW as usize) {
+        for wx in (0..expected.width()).step_by(WINDOW as usize) {
+            let mut a = Vec::with_capacity((WINDOW * WINDOW) as usize);
+            let mut b = Vec::with_capacity((WINDOW * WINDOW) as usize);
+            for y in wy..(wy + WINDOW).min(expected.height()) {
+                for x in wx..(wx + WINDOW).min(expected.width()) {
+                    a.push(luma(expected.get_pixel(x, y)));
+                    b.push(luma(actual.get_pixel(x, y)));
+                }
+            }
+
+            let n = a.len() as f32;
+            let mean_a = a.iter().sum::<f32>() / n;
+            let mean_b = b.iter().sum::<f32>() / n;
+            let mut var_a = 0.0;
+            let mut var_b = 0.0;
+            let mut covar = 0.0;
+            for (pa, pb) in a.iter().zip(b.iter()) {
+                var_a += (pa - mean_a) * (pa - mean_a);
+                var_b += (pb - mean_b) * (pb - mean_b);
+                covar += (pa - mean_a) * (pb - mean_b);
+            }
+            var_a /= n;
+            var_b /= n;
+            covar /= n;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+
+    if windows == 0 { 1.0 } else { total / windows as f32 }
+}
+
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, tolerance: &Tolerance) -> Comparison {
+    let mut mismatched = 0;
+    let mut max_delta = 0;
+    for (a, b) in expected.pixels().zip(actual.pixels()) {
+        let delta = channel_delta(a, b);
+        max_delta = max_delta.max(delta);
+        if delta > tolerance.channel {
+            mismatched += 1;
+        }
+    }
+
+    Comparison {
+        mismatched,
+        total: (expected.width() * expected.height()) as usize,
+        max_delta,
+        ssim: ssim(expected, actual)
+    }
+}
+
+/// Mismatched pixels are drawn red, scaled by how far off they are, over a
+/// dimmed copy of the expected image.
+pub fn diff_image(expected: &RgbaImage, actual: &RgbaImage, tolerance: &Tolerance) -> RgbaImage {
+    RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let a = expected.get_pixel(x, y);
+        let b = actual.get_pixel(x, y);
+        let delta = channel_delta(a, b);
+        if delta > tolerance.channel {
+            Rgba([128u8.saturating_add(delta / 2), 0, 0, 255])
+        } else {
+            let gray = (luma(a) * 0.25) as u8;
+            Rgba([gray, gray, gray, 255])
+        }
+    })
+}
+
+/// Compares `actual` against the stored golden for `golden`, writing the actual
+/// and diff images to `diff_dir()` on failure.
+pub fn check(golden: &GoldenScene, actual: &RgbaImage, tolerance: &Tolerance) -> Result<(), String> {
+    let name = golden.name;
+    let golden_path = golden.golden_path();
+
+    if std::env::var_os(BLESS_ENV).is_some() {
+        if let Some(dir) = golden_path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        actual.save(&golden_path).map_err(|e| e.to_string())?;
+        println!("Blessed {}", golden_path.display());
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(diff_dir()).map_err(|e| e.to_string())?;
+    let actual_path = diff_dir().join(format!("{}.actual.png", name));
+
+    let expected = match image::open(&golden_path) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => {
+            actual.save(&actual_path).map_err(|e| e.to_string())?;
+            return Err(format!("{}: could not read {} ({}); run with {}=1 to create it", name, golden_path.display(), e, BLESS_ENV));
+        }
+    };
+
+    if expected.dimensions() != actual.dimensions() {
+        actual.save(&actual_path).map_err(|e| e.to_string())?;
+        return Err(format!("{}: golden is {:?} but rendered {:?}", name, expected.dimensions(), actual.dimensions()));
+    }
+
+    let comparison = compare(&expected, actual, tolerance);
+    if comparison.passes(tolerance) {
+        return Ok(());
+    }
+
+    let diff_path = diff_dir().join(format!("{}.diff.png", name));
+    actual.save(&actual_path).map_err(|e| e.to_string())?;
+    diff_image(&expected, actual, tolerance).save(&diff_path).map_err(|e| e.to_string())?;
+    Err(format!(
+        "{}: {} of {} pixels differ (max delta {}), ssim {:.4}; see {}",
+        name, comparison.mismatched, comparison.total, comparison.max_delta, comparison.ssim, diff_path.display()
+    ))
+}
+
+pub fn render_scene(renderer: &mut Renderer, scene: &mut Scene, golden: &GoldenScene) -> RgbaImage {
+    renderer.debug_view = golden.view;
+    scene.reset().unwrap();
+    renderer.materials.get_mut(scene.atlas_material).unwrap().specular_attributes = default_specular_attributes();
+    (golden.setup)(renderer, scene);
+    renderer.prepare(scene);
+    headless::render_to_image(renderer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use glam::uvec2;
+
+    use crate::{renderer::RendererError, scene::{SceneDescription, DEFAULT_SCENE}};
+    use super::*;
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(64, 32, |x, y| Rgba([(x * 4) as u8, (y * 8) as u8, 128, 255]))
+    }
+
+    #[test]
+    fn identical_images_pass() {
+        let image = gradient();
+        let tolerance = Tolerance::default();
+        let comparison = compare(&image, &image, &tolerance);
+        assert_eq!(comparison.mismatched, 0);
+        assert!(comparison.passes(&tolerance));
+    }
+
+    #[test]
+    fn flipped_image_fails() {
+        let image = gradient();
+        let flipped = image::imageops::flip_horizontal(&image);
+        let tolerance = Tolerance::default();
+        assert!(!compare(&image, &flipped, &tolerance).passes(&tolerance));
+    }
+
+    #[test]
+    fn every_scene_has_a_golden() {
+        for golden in scenes() {
+            assert!(golden.golden_path().is_file(), "no golden for {}", golden.name);
+        }
+    }
+
+    #[test]
+    fn debug_view_goldens_are_the_readme_images() {
+        for view in DebugView::ALL {
+            let golden = scenes().into_iter().find(|golden| golden.name == view.name()).unwrap();
+            assert_eq!(golden.golden_path(), reference_dir().join(format!("{}.png", view.name())));
+        }
+    }
+
+    /// Skipped when neither a hardware nor a software adapter is available.
+    #[test]
+    fn lighting_goldens() {
+        let renderer = futures::executor::block_on(Renderer::builder(uvec2(GOLDEN_SIZE.width, GOLDEN_SIZE.height)).build_headless());
+        let mut renderer = match renderer {
+            Ok(renderer) => renderer,
+            Err(RendererError::NoAdapter) => {
+                // Written past the test harness's capture so the skip shows up in a plain `cargo test`.
+                let _ = writeln!(std::io::stderr(), "skipping lighting_goldens: no hardware or software adapter available");
+                return;
+            },
+            Err(error) => panic!("{}", error)
+        };
+        let description = SceneDescription::load(&renderer.assets.resolve(DEFAULT_SCENE)).unwrap();
+        let mut scene = Scene::load(&mut renderer, description).unwrap();
+
+        let tolerance = Tolerance::default();
+        let failures: Vec<String> = scenes().iter()
+            .filter_map(|golden| check(golden, &render_scene(&mut renderer, &mut scene, golden), &tolerance).err())
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
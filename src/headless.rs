@@ -0,0 +1,17 @@
+use crate::renderer::Renderer;
+
+/// Renders a single frame of the prepared scene into a headless renderer's
+/// offscreen target and reads it back.
+pub fn render_to_image(renderer: &Renderer) -> image::RgbaImage {
+    let target = renderer.offscreen_target().expect("render_to_image needs a headless renderer");
+
+    let mut encoder = renderer.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("headless_encoder") });
+    renderer.render(&target.view, &mut encoder);
+    renderer.queue.submit(core::iter::once(encoder.finish()));
+
+    target.read_to_image(&renderer.device, &renderer.queue)
+}
+
+pub fn render_to_png(renderer: &Renderer, path: &str) -> Result<(), image::ImageError> {
+    render_to_image(renderer).save(path)
+}
@@ -0,0 +1,14 @@
+//! Orthographic forward renderer for normal mapped sprites lit by point,
+//! directional and spot lights.
+
+pub mod components;
+pub mod headless;
+pub mod render;
+pub mod renderer;
+pub mod scene;
+
+#[cfg(test)]
+mod golden;
+
+pub use renderer::{Renderer, RendererBuilder, RendererError};
+pub use scene::{Scene, SceneDescription, SceneError};
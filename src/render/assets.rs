@@ -0,0 +1,532 @@
+use std::{collections::HashMap, fmt, hash::{Hash, Hasher}, marker::PhantomData, path::{Path, PathBuf}, sync::{Arc, Weak}, time::SystemTime};
+
+use image::DynamicImage;
+use wgpu::{Device, Queue};
+
+use super::{MipmapGenerator, NormalMapConvention, SamplerCache, SamplerSettings, Texture, TextureOptions};
+
+/// Where assets are loaded from unless another directory is given.
+pub const DEFAULT_ASSET_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res");
+
+/// The renderer's shaders, compiled in so a build runs without `res/`. A file
+/// of the same name under the asset directory overrides one of them.
+pub const BUILTIN_SHADERS: &[(&str, &str)] = &[
+    ("bloom.wgsl", include_str!("../../res/bloom.wgsl")),
+    ("frag.wgsl", include_str!("../../res/frag.wgsl")),
+    ("light_cull.wgsl", include_str!("../../res/light_cull.wgsl")),
+    ("light_tiles_storage.wgsl", include_str!("../../res/light_tiles_storage.wgsl")),
+    ("light_tiles_uniform.wgsl", include_str!("../../res/light_tiles_uniform.wgsl")),
+    ("mipmap.wgsl", include_str!("../../res/mipmap.wgsl")),
+    ("tonemap.wgsl", include_str!("../../res/tonemap.wgsl")),
+    ("vertex.wgsl", include_str!("../../res/vertex.wgsl"))
+];
+
+pub fn builtin_shader(name: &str) -> Option<&'static str> {
+    BUILTIN_SHADERS.iter().find(|(builtin, _)| *builtin == name).map(|(_, source)| *source)
+}
+
+/// A counted reference to an asset in `Assets<T>`. The asset stays loaded
+/// while any clone of its handle is alive.
+pub struct Handle<T> {
+    id: usize,
+    refs: Arc<()>,
+    marker: PhantomData<fn() -> T>
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self { id: self.id, refs: self.refs.clone(), marker: PhantomData }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({})", self.id)
+    }
+}
+
+struct AssetEntry<T> {
+    asset: T,
+    path: Option<PathBuf>,
+    refs: Weak<()>
+}
+
+/// Storage for one asset type. Assets loaded from a path are deduplicated by
+/// that path, and assets whose handles have all been dropped are kept until
+/// `free_unused`.
+pub struct Assets<T> {
+    entries: Vec<Option<AssetEntry<T>>>,
+    by_path: HashMap<PathBuf, usize>,
+    free: Vec<usize>
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            by_path: HashMap::new(),
+            free: Vec::new()
+        }
+    }
+}
+
+impl<T> Assets<T> {
+    /// Stores an asset that wasn't loaded from a file, such as a packed atlas.
+    pub fn add(&mut self, asset: T) -> Handle<T> {
+        self.insert(asset, None)
+    }
+
+    fn insert(&mut self, asset: T, path: Option<PathBuf>) -> Handle<T> {
+        let refs = Arc::new(());
+        let entry = AssetEntry { asset, path: path.clone(), refs: Arc::downgrade(&refs) };
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.entries[id] = Some(entry);
+                id
+            },
+            None => {
+                self.entries.push(Some(entry));
+                self.entries.len() - 1
+            }
+        };
+        if let Some(path) = path {
+            self.by_path.insert(path, id);
+        }
+        Handle { id, refs, marker: PhantomData }
+    }
+
+    /// A new handle to the asset already loaded from `path`, if any.
+    pub fn handle_for_path(&mut self, path: &Path) -> Option<Handle<T>> {
+        let id = *self.by_path.get(path)?;
+        let entry = self.entries[id].as_mut().unwrap();
+        let refs = entry.refs.upgrade().unwrap_or_else(|| {
+            let refs = Arc::new(());
+            entry.refs = Arc::downgrade(&refs);
+            refs
+        });
+        Some(Handle { id, refs, marker: PhantomData })
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> &T {
+        &self.entries[handle.id].as_ref().unwrap().asset
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> &mut T {
+        &mut self.entries[handle.id].as_mut().unwrap().asset
+    }
+
+    pub fn contains_path(&self, path: &Path) -> bool {
+        self.by_path.contains_key(path)
+    }
+
+    pub fn path(&self, handle: &Handle<T>) -> Option<&Path> {
+        self.entries[handle.id].as_ref().unwrap().path.as_deref()
+    }
+
+    /// Number of live handles to the asset.
+    pub fn ref_count(&self, handle: &Handle<T>) -> usize {
+        Arc::strong_count(&handle.refs)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every asset without a live handle, returning how many were freed.
+    /// Dropping a `Texture` releases its GPU memory.
+    pub fn free_unused(&mut self) -> usize {
+        self.take_unused().len()
+    }
+
+    /// Frees like `free_unused`, returning the path of each freed asset.
+    fn take_unused(&mut self) -> Vec<Option<PathBuf>> {
+        let mut freed = Vec::new();
+        for (id, slot) in self.entries.iter_mut().enumerate() {
+            if slot.as_ref().is_some_and(|entry| entry.refs.strong_count() == 0) {
+                let path = slot.take().unwrap().path;
+                if let Some(path) = path.as_ref() {
+                    self.by_path.remove(path);
+                }
+                self.free.push(id);
+                freed.push(path);
+            }
+        }
+        freed
+    }
+}
+
+/// WGSL source loaded from the asset directory.
+pub struct Shader {
+    pub source: String
+}
+
+/// How a texture file's pixels are interpreted on upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureKind {
+    /// sRGB color, such as albedo or emissive maps.
+    Color,
+    /// Linear data, such as specular or gloss masks.
+    Data,
+    Normal(NormalMapConvention)
+}
+
+#[derive(Debug)]
+pub enum AssetError {
+    Io { path: PathBuf, error: std::io::Error },
+    Decode { path: PathBuf, error: image::ImageError }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Io { path, error } => write!(f, "failed to read {}: {}", path.display(), error),
+            AssetError::Decode { path, error } => write!(f, "failed to decode {}: {}", path.display(), error)
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+/// Loads textures and shaders from files under `root` at runtime, and
+/// remembers the modification time of every file read for hot reloading.
+pub struct AssetManager {
+    pub root: PathBuf,
+    pub textures: Assets<Texture>,
+    pub shaders: Assets<Shader>,
+    /// Builds mip chains for every texture loaded while set. Set by
+    /// `RendererBuilder::with_filtering`.
+    pub mipmaps: Option<MipmapGenerator>,
+    /// Whether the renderer's material layout takes filtering samplers. Set by
+    /// `RendererBuilder::with_filtering`; without it textures sample nearest.
+    pub filtering: bool,
+    /// Sampling for textures loaded with `load_texture`; trilinear after
+    /// `RendererBuilder::with_filtering`, otherwise pixel art.
+    pub sampler: SamplerSettings,
+    pub samplers: SamplerCache,
+    texture_kinds: HashMap<PathBuf, (TextureKind, SamplerSettings)>,
+    watched: HashMap<PathBuf, Option<SystemTime>>
+}
+
+impl Default for AssetManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_ASSET_DIR)
+    }
+}
+
+impl AssetManager {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            textures: Assets::default(),
+            shaders: Assets::default(),
+            mipmaps: None,
+            filtering: false,
+            sampler: SamplerSettings::default(),
+            samplers: SamplerCache::default(),
+            texture_kinds: HashMap::new(),
+            watched: HashMap::new()
+        }
+    }
+
+    /// `path` relative to the asset directory. Absolute paths are left as they are.
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+
+    pub fn read(&mut self, path: &str) -> Result<Vec<u8>, AssetError> {
+        let path = self.resolve(path);
+        self.read_resolved(&path)
+    }
+
+    /// Like `read` for a path that was already resolved, such as one returned
+    /// by `poll_changes`.
+    pub fn read_resolved(&mut self, path: &Path) -> Result<Vec<u8>, AssetError> {
+        self.watched.insert(path.to_path_buf(), modified(path));
+        std::fs::read(path).map_err(|error| AssetError::Io { path: path.to_path_buf(), error })
+    }
+
+    fn read_to_string(&mut self, path: &Path) -> Result<String, AssetError> {
+        let bytes = self.read_resolved(path)?;
+        String::from_utf8(bytes).map_err(|error| AssetError::Io {
+            path: path.to_path_buf(),
+            error: std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+        })
+    }
+
+    /// Decodes an image without uploading or caching it, for packing into an atlas.
+    pub fn load_image(&mut self, path: &str) -> Result<DynamicImage, AssetError> {
+        let bytes = self.read(path)?;
+        image::load_from_memory(&bytes).map_err(|error| AssetError::Decode { path: self.resolve(path), error })
+    }
+
+    /// Uploads the texture at `path` sampled with `self.sampler`, or returns
+    /// the handle it was already loaded under.
+    pub fn load_texture(&mut self, device: &Device, queue: &Queue, path: &str, kind: TextureKind) -> Result<Handle<Texture>, AssetError> {
+        self.load_texture_with(device, queue, path, kind, self.sampler)
+    }
+
+    /// Like `load_texture` with its own sampler settings, such as repeating
+    /// for a tiled floor. A path already loaded keeps its original settings.
+    /// Linear filters become nearest unless `filtering` is set, since the
+    /// material layout would reject them.
+    pub fn load_texture_with(&mut self, device: &Device, queue: &Queue, path: &str, kind: TextureKind, sampler: SamplerSettings) -> Result<Handle<Texture>, AssetError> {
+        let sampler = self.supported_sampler(sampler);
+        let resolved = self.resolve(path);
+        if let Some(handle) = self.textures.handle_for_path(&resolved) {
+            return Ok(handle);
+        }
+
+        let texture = self.decode_texture(device, queue, &resolved, kind, sampler)?;
+        self.texture_kinds.insert(resolved.clone(), (kind, sampler));
+        Ok(self.textures.insert(texture, Some(resolved)))
+    }
+
+    /// `sampler`, or its nearest equivalent when filtering isn't available.
+    pub fn supported_sampler(&self, sampler: SamplerSettings) -> SamplerSettings {
+        if self.filtering || !sampler.is_filtering() { sampler } else { sampler.without_filtering() }
+    }
+
+    fn decode_texture(&mut self, device: &Device, queue: &Queue, path: &Path, kind: TextureKind, sampler: SamplerSettings) -> Result<Texture, AssetError> {
+        let bytes = self.read_resolved(path)?;
+        let label = &path.to_string_lossy();
+        let options = TextureOptions {
+            normal_convention: None,
+            sampler,
+            mipmaps: self.mipmaps.is_some(),
+            max_mip_levels: None
+        };
+        match kind {
+            TextureKind::Color => Texture::from_bytes(device, queue, &bytes, label, &options, &mut self.samplers, self.mipmaps.as_mut()),
+            TextureKind::Data => Texture::data_from_bytes(device, queue, &bytes, label, &options, &mut self.samplers, self.mipmaps.as_mut()),
+            TextureKind::Normal(convention) => {
+                let options = TextureOptions { normal_convention: Some(convention), ..options };
+                Texture::from_bytes(device, queue, &bytes, label, &options, &mut self.samplers, self.mipmaps.as_mut())
+            }
+        }.map_err(|error| AssetError::Decode { path: path.to_path_buf(), error })
+    }
+
+    pub fn load_shader(&mut self, path: &str) -> Result<Handle<Shader>, AssetError> {
+        let resolved = self.resolve(path);
+        if let Some(handle) = self.shaders.handle_for_path(&resolved) {
+            return Ok(handle);
+        }
+
+        let source = self.read_shader(&resolved)?;
+        Ok(self.shaders.insert(Shader { source }, Some(resolved)))
+    }
+
+    /// The file at `path` if there is one, otherwise the built-in shader it
+    /// would override. A missing override is still watched, so creating or
+    /// deleting it is picked up by hot reloading.
+    fn read_shader(&mut self, path: &Path) -> Result<String, AssetError> {
+        let builtin = match path.strip_prefix(&self.root).ok().and_then(Path::to_str) {
+            Some(name) if !path.is_file() => builtin_shader(name),
+            _ => None
+        };
+        match builtin {
+            Some(source) => {
+                self.watched.insert(path.to_path_buf(), None);
+                Ok(source.to_string())
+            },
+            None => self.read_to_string(path)
+        }
+    }
+
+    pub fn shader_source(&self, handle: &Handle<Shader>) -> &str {
+        &self.shaders.get(handle).source
+    }
+
+    /// Files read since the last call whose modification time has changed.
+    pub fn poll_changes(&mut self) -> Vec<PathBuf> {
+        self.watched.iter_mut()
+            .filter_map(|(path, last)| {
+                let current = modified(path);
+                (current != *last).then(|| {
+                    *last = current;
+                    path.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Re-reads a loaded shader in place, returning its handle, or `None` if
+    /// no shader was loaded from `path`. A deleted override goes back to the
+    /// built-in shader. On failure the old source is kept.
+    pub fn reload_shader(&mut self, path: &Path) -> Result<Option<Handle<Shader>>, AssetError> {
+        let handle = match self.shaders.handle_for_path(path) {
+            Some(handle) => handle,
+            None => return Ok(None)
+        };
+        let source = self.read_shader(path)?;
+        self.shaders.get_mut(&handle).source = source;
+        Ok(Some(handle))
+    }
+
+    /// Re-uploads a loaded texture in place, returning its handle, or `None`
+    /// if no texture was loaded from `path`. On failure the old texture is kept.
+    /// Bind groups holding the old texture must be rebuilt.
+    pub fn reload_texture(&mut self, device: &Device, queue: &Queue, path: &Path) -> Result<Option<Handle<Texture>>, AssetError> {
+        let (handle, (kind, sampler)) = match (self.textures.handle_for_path(path), self.texture_kinds.get(path)) {
+            (Some(handle), Some(settings)) => (handle, *settings),
+            _ => return Ok(None)
+        };
+        let texture = self.decode_texture(device, queue, path, kind, sampler)?;
+        *self.textures.get_mut(&handle) = texture;
+        Ok(Some(handle))
+    }
+
+    /// Frees textures and shaders that nothing holds a handle to, and stops
+    /// watching the files they were loaded from.
+    pub fn free_unused(&mut self) -> usize {
+        let mut freed = self.textures.take_unused();
+        freed.extend(self.shaders.take_unused());
+        for path in freed.iter().flatten() {
+            self.texture_kinds.remove(path);
+            self.watched.remove(path);
+        }
+        freed.len()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_deduplicated_while_referenced() {
+        let mut assets = Assets::default();
+        let path = PathBuf::from("res/a.wgsl");
+        let handle = assets.insert("a", Some(path.clone()));
+        let again = assets.handle_for_path(&path).unwrap();
+        assert_eq!(handle, again);
+        assert_eq!(assets.ref_count(&handle), 2);
+        assert!(assets.handle_for_path(Path::new("res/b.wgsl")).is_none());
+    }
+
+    #[test]
+    fn unused_assets_are_freed_and_slots_reused() {
+        let mut assets = Assets::default();
+        let kept = assets.add("kept");
+        let path = PathBuf::from("res/dropped.png");
+        let dropped = assets.insert("dropped", Some(path.clone()));
+        let id = dropped.id();
+
+        let clone = dropped.clone();
+        drop(dropped);
+        assert_eq!(assets.free_unused(), 0);
+        drop(clone);
+        assert_eq!(assets.free_unused(), 1);
+        assert_eq!(assets.len(), 1);
+        assert!(assets.handle_for_path(&path).is_none());
+        assert_eq!(*assets.get(&kept), "kept");
+        assert_eq!(assets.add("new").id(), id);
+    }
+
+    #[test]
+    fn filtering_samplers_need_a_filtering_layout() {
+        let mut manager = AssetManager::default();
+        let tiled = SamplerSettings::trilinear().repeat();
+        assert_eq!(manager.supported_sampler(tiled), SamplerSettings::pixel_art().repeat());
+        manager.filtering = true;
+        assert_eq!(manager.supported_sampler(tiled), tiled);
+    }
+
+    #[test]
+    fn missing_files_report_the_path() {
+        let mut manager = AssetManager::new("does/not/exist");
+        match manager.load_image("missing.png") {
+            Err(AssetError::Io { path, .. }) => assert_eq!(path, Path::new("does/not/exist/missing.png")),
+            other => panic!("expected an io error, got {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn edited_shaders_are_reported_and_reloaded() {
+        let dir = std::env::temp_dir().join(format!("asset_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.wgsl"), "// one").unwrap();
+
+        let mut manager = AssetManager::new(&dir);
+        let handle = manager.load_shader("a.wgsl").unwrap();
+        assert!(manager.poll_changes().is_empty());
+
+        // Not every filesystem records sub-second modification times.
+        let file = std::fs::File::options().write(true).open(dir.join("a.wgsl")).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(5)).unwrap();
+        std::fs::write(dir.join("a.wgsl"), "// two").unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(5)).unwrap();
+
+        let changed = manager.poll_changes();
+        assert_eq!(changed, vec![dir.join("a.wgsl")]);
+        assert_eq!(manager.reload_shader(&changed[0]).unwrap(), Some(handle.clone()));
+        assert_eq!(manager.shader_source(&handle), "// two");
+
+        drop(handle);
+        assert_eq!(manager.free_unused(), 1);
+        assert!(!manager.watched.contains_key(&dir.join("a.wgsl")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reloads_read_from_a_relative_root() {
+        let dir = PathBuf::from("target").join(format!("asset_reload_relative_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.wgsl"), "// one").unwrap();
+
+        let mut manager = AssetManager::new(&dir);
+        let handle = manager.load_shader("a.wgsl").unwrap();
+        std::fs::write(dir.join("a.wgsl"), "// two").unwrap();
+
+        let path = manager.resolve("a.wgsl");
+        assert_eq!(manager.reload_shader(&path).unwrap(), Some(handle.clone()));
+        assert_eq!(manager.shader_source(&handle), "// two");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn builtin_shaders_load_without_files_and_can_be_overridden() {
+        let dir = std::env::temp_dir().join(format!("asset_builtin_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("vertex.wgsl"), "// override").unwrap();
+
+        let mut manager = AssetManager::new(&dir);
+        let frag = manager.load_shader("frag.wgsl").unwrap();
+        assert_eq!(manager.shader_source(&frag), builtin_shader("frag.wgsl").unwrap());
+        let vertex = manager.load_shader("vertex.wgsl").unwrap();
+        assert_eq!(manager.shader_source(&vertex), "// override");
+        assert!(manager.load_shader("missing.wgsl").is_err());
+
+        std::fs::remove_file(dir.join("vertex.wgsl")).unwrap();
+        manager.reload_shader(&dir.join("vertex.wgsl")).unwrap();
+        assert_eq!(manager.shader_source(&vertex), builtin_shader("vertex.wgsl").unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
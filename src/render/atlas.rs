@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+
+use glam::{UVec2, Vec2, uvec2};
+use image::{DynamicImage, Rgba, RgbaImage};
+use wgpu::{Device, Queue};
+
+use super::{MipmapGenerator, NormalMapConvention, SamplerCache, Texture, TextureOptions};
+
+/// Used for regions added without a normal map. Points straight out of the
+/// texture in every convention, including two-channel maps.
+const FLAT_NORMAL: Rgba<u8> = Rgba([128, 128, 255, 255]);
+
+/// A named sub-rectangle of an atlas, in pixels and in normalized UVs with
+/// `uv_min` at the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasRegion {
+    pub name: String,
+    pub position: UVec2,
+    pub size: UVec2,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2
+}
+
+/// Layout shared by an albedo atlas and its parallel normal atlas.
+#[derive(Debug, Clone, Default)]
+pub struct TextureAtlas {
+    pub size: UVec2,
+    pub regions: HashMap<String, AtlasRegion>
+}
+
+impl TextureAtlas {
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+}
+
+#[derive(Debug)]
+pub enum AtlasError {
+    /// The images don't fit within `max_size` on either axis.
+    TooLarge { max_size: u32 },
+    NormalSizeMismatch { name: String, albedo: UVec2, normal: UVec2 },
+    DuplicateName(String)
+}
+
+impl std::fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtlasError::TooLarge { max_size } => write!(f, "atlas images do not fit in {}x{}", max_size, max_size),
+            AtlasError::NormalSizeMismatch { name, albedo, normal } =>
+                write!(f, "normal map for {} is {}x{} but the albedo is {}x{}", name, normal.x, normal.y, albedo.x, albedo.y),
+            AtlasError::DuplicateName(name) => write!(f, "atlas region {} was added twice", name)
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+struct AtlasEntry {
+    name: String,
+    albedo: RgbaImage,
+    normal: Option<RgbaImage>
+}
+
+/// The packed albedo and normal images with their shared layout.
+pub struct BuiltAtlas {
+    pub atlas: TextureAtlas,
+    pub albedo: RgbaImage,
+    /// Every region encoded in `normal_convention`.
+    pub normal: RgbaImage,
+    pub normal_convention: NormalMapConvention,
+    /// Edge extrusion around each region.
+    pub padding: u32
+}
+
+/// Mip levels that sample only a region and its padding. At level `n` the
+/// texel on a region's edge can reach `2^n - 1` pixels past it and bilinear
+/// filtering adds the next texel, so the level needs `2^(n + 1) - 1` pixels
+/// of padding.
+pub fn padded_mip_levels(padding: u32) -> u32 {
+    (padding + 1).ilog2().max(1)
+}
+
+impl BuiltAtlas {
+    /// Uploads the albedo as sRGB and the normal atlas in its convention,
+    /// sharing the sampler and mip settings. The options' normal convention is
+    /// ignored, and the mip chain is capped at what the padding covers.
+    pub fn upload(&self, device: &Device, queue: &Queue, label: &str, options: &TextureOptions, samplers: &mut SamplerCache, mut mipmaps: Option<&mut MipmapGenerator>) -> (Texture, Texture) {
+        let max_mip_levels = padded_mip_levels(self.padding).min(options.max_mip_levels.unwrap_or(u32::MAX));
+        let options = &options.with_max_mip_levels(max_mip_levels);
+        let albedo_options = TextureOptions { normal_convention: None, ..*options };
+        let albedo = Texture::from_image(device, queue, &DynamicImage::ImageRgba8(self.albedo.clone()), &format!("{}_albedo", label), &albedo_options, samplers, mipmaps.as_deref_mut());
+        let normal_options = TextureOptions { normal_convention: Some(self.normal_convention), ..*options };
+        let normal = Texture::from_image(device, queue, &DynamicImage::ImageRgba8(self.normal.clone()), &format!("{}_normal", label), &normal_options, samplers, mipmaps);
+        (albedo, normal)
+    }
+}
+
+/// Packs images into rows (shelves), tallest first. Each image is surrounded
+/// by `padding` pixels copied from its own edges, so filtering and mipmapping
+/// near a region's border never pull in a neighbour.
+pub struct AtlasBuilder {
+    pub padding: u32,
+    pub max_size: u32,
+    /// Convention of the packed normal atlas; normal maps in other
+    /// conventions are converted to it as they are added.
+    pub normal_convention: NormalMapConvention,
+    entries: Vec<AtlasEntry>
+}
+
+impl AtlasBuilder {
+    pub fn new(padding: u32) -> Self {
+        Self {
+            padding,
+            max_size: 2048,
+            normal_convention: NormalMapConvention::YUp,
+            entries: Vec::new()
+        }
+    }
+
+    /// Caps the atlas size, usually at `Limits::max_texture_dimension_2d`.
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn with_normal_convention(mut self, convention: NormalMapConvention) -> Self {
+        self.normal_convention = convention;
+        self
+    }
+
+    /// Adds a region whose normal map, if any, is already in the atlas's convention.
+    pub fn add(&mut self, name: &str, albedo: &DynamicImage, normal: Option<&DynamicImage>) -> Result<(), AtlasError> {
+        self.add_with_convention(name, albedo, normal, self.normal_convention)
+    }
+
+    /// Adds a region whose normal map is in `convention`, converting it to the atlas's.
+    pub fn add_with_convention(&mut self, name: &str, albedo: &DynamicImage, normal: Option<&DynamicImage>, convention: NormalMapConvention) -> Result<(), AtlasError> {
+        if self.entries.iter().any(|entry| entry.name == name) {
+            return Err(AtlasError::DuplicateName(name.to_string()));
+        }
+
+        let albedo = albedo.to_rgba8();
+        let normal = normal.map(|normal| normal.to_rgba8());
+        if let Some(normal) = normal.as_ref() {
+            if normal.dimensions() != albedo.dimensions() {
+                return Err(AtlasError::NormalSizeMismatch {
+                    name: name.to_string(),
+                    albedo: albedo.dimensions().into(),
+                    normal: normal.dimensions().into()
+                });
+            }
+        }
+
+        let normal = normal.map(|normal| convention.convert(&normal, self.normal_convention));
+        self.entries.push(AtlasEntry { name: name.to_string(), albedo, normal });
+        Ok(())
+    }
+
+    fn padded_size(&self, entry: &AtlasEntry) -> UVec2 {
+        UVec2::from(entry.albedo.dimensions()) + UVec2::splat(self.padding * 2)
+    }
+
+    /// Shelf packs the padded images at `width`, returning each entry's padded
+    /// top left corner and the total height.
+    fn pack(&self, order: &[usize], width: u32) -> Option<(Vec<UVec2>, u32)> {
+        let mut positions = vec![UVec2::ZERO; self.entries.len()];
+        let mut cursor = UVec2::ZERO;
+        let mut shelf_height = 0;
+        for &index in order {
+            let size = self.padded_size(&self.entries[index]);
+            if size.x > width {
+                return None;
+            }
+            if cursor.x + size.x > width {
+                cursor = uvec2(0, cursor.y + shelf_height);
+                shelf_height = 0;
+            }
+            positions[index] = cursor;
+            cursor.x += size.x;
+            shelf_height = shelf_height.max(size.y);
+        }
+        Some((positions, cursor.y + shelf_height))
+    }
+
+    pub fn build(&self) -> Result<BuiltAtlas, AtlasError> {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.padded_size(&self.entries[index]).y));
+
+        let area: u32 = self.entries.iter().map(|entry| self.padded_size(entry)).map(|size| size.x * size.y).sum();
+        let widest = self.entries.iter().map(|entry| self.padded_size(entry).x).max().unwrap_or(1);
+        let mut width = ((area as f32).sqrt().ceil() as u32).max(widest).next_power_of_two().min(self.max_size);
+
+        // Widen until the atlas is no taller than it is wide, or as wide as allowed.
+        let (positions, height) = loop {
+            match self.pack(&order, width).filter(|(_, height)| *height <= self.max_size) {
+                Some((positions, height)) if height <= width || width >= self.max_size => break (positions, height),
+                None if width >= self.max_size => return Err(AtlasError::TooLarge { max_size: self.max_size }),
+                _ => width = (width * 2).min(self.max_size)
+            }
+        };
+
+        let size = uvec2(width, height.max(1));
+        let mut albedo = RgbaImage::new(size.x, size.y);
+        let mut normal = RgbaImage::from_pixel(size.x, size.y, FLAT_NORMAL);
+        let mut atlas = TextureAtlas { size, regions: HashMap::new() };
+        for (entry, padded_position) in self.entries.iter().zip(positions) {
+            let position = padded_position + UVec2::splat(self.padding);
+            blit_extruded(&mut albedo, &entry.albedo, position, self.padding);
+            if let Some(normal_image) = entry.normal.as_ref() {
+                blit_extruded(&mut normal, normal_image, position, self.padding);
+            }
+
+            let region_size = UVec2::from(entry.albedo.dimensions());
+            atlas.regions.insert(entry.name.clone(), AtlasRegion {
+                name: entry.name.clone(),
+                position,
+                size: region_size,
+                uv_min: position.as_vec2() / size.as_vec2(),
+                uv_max: (position + region_size).as_vec2() / size.as_vec2()
+            });
+        }
+
+        Ok(BuiltAtlas { atlas, albedo, normal, normal_convention: self.normal_convention, padding: self.padding })
+    }
+}
+
+/// Copies `source` to `position` and fills `padding` pixels around it with
+/// the nearest edge pixel.
+fn blit_extruded(target: &mut RgbaImage, source: &RgbaImage, position: UVec2, padding: u32) {
+    let (width, height) = source.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+    let padding = padding as i64;
+    for y in -padding..height as i64 + padding {
+        for x in -padding..width as i64 + padding {
+            let pixel = source.get_pixel(x.clamp(0, width as i64 - 1) as u32, y.clamp(0, height as i64 - 1) as u32);
+            target.put_pixel((position.x as i64 + x) as u32, (position.y as i64 + y) as u32, *pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, Rgba([value, value, value, 255])))
+    }
+
+    #[test]
+    fn regions_do_not_overlap_including_padding() {
+        let mut builder = AtlasBuilder::new(2);
+        for (i, (width, height)) in [(16, 16), (30, 8), (8, 30), (5, 5), (20, 12)].into_iter().enumerate() {
+            builder.add(&format!("image_{}", i), &solid(width, height, i as u8 * 40), None).unwrap();
+        }
+        let built = builder.build().unwrap();
+
+        let padded: Vec<(UVec2, UVec2)> = built.atlas.regions.values()
+            .map(|region| (region.position - UVec2::splat(2), region.position + region.size + UVec2::splat(2)))
+            .collect();
+        for (i, a) in padded.iter().enumerate() {
+            assert!(a.1.x <= built.atlas.size.x && a.1.y <= built.atlas.size.y);
+            for b in padded.iter().skip(i + 1) {
+                let disjoint = a.1.x <= b.0.x || b.1.x <= a.0.x || a.1.y <= b.0.y || b.1.y <= a.0.y;
+                assert!(disjoint, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn padding_extrudes_edges_and_normals_share_layout() {
+        let mut builder = AtlasBuilder::new(1);
+        builder.add("lit", &solid(4, 4, 200), Some(&solid(4, 4, 60))).unwrap();
+        builder.add("flat", &solid(4, 4, 100), None).unwrap();
+        let built = builder.build().unwrap();
+
+        let lit = built.atlas.region("lit").unwrap();
+        let corner = lit.position - UVec2::ONE;
+        assert_eq!(built.albedo.get_pixel(corner.x, corner.y)[0], 200);
+        assert_eq!(built.normal.get_pixel(corner.x, corner.y)[0], 60);
+
+        let flat = built.atlas.region("flat").unwrap();
+        assert_eq!(*built.normal.get_pixel(flat.position.x, flat.position.y), FLAT_NORMAL);
+    }
+
+    #[test]
+    fn normals_are_converted_to_the_atlas_convention() {
+        let texel = |r, g, b| DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([r, g, b, 255])));
+        let mut builder = AtlasBuilder::new(0).with_normal_convention(NormalMapConvention::YDown);
+        builder.add("down", &solid(1, 1, 0), Some(&texel(128, 200, 220))).unwrap();
+        builder.add_with_convention("up", &solid(1, 1, 0), Some(&texel(128, 200, 220)), NormalMapConvention::YUp).unwrap();
+        builder.add_with_convention("rg", &solid(1, 1, 0), Some(&texel(128, 128, 0)), NormalMapConvention::TwoChannel { y_down: true }).unwrap();
+        let built = builder.build().unwrap();
+
+        let normal = |name: &str| {
+            let position = built.atlas.region(name).unwrap().position;
+            *built.normal.get_pixel(position.x, position.y)
+        };
+        assert_eq!(normal("down"), Rgba([128, 200, 220, 255]));
+        assert_eq!(normal("up"), Rgba([128, 55, 220, 255]));
+        assert_eq!(normal("rg"), Rgba([128, 128, 255, 255]));
+        assert_eq!(built.normal_convention, NormalMapConvention::YDown);
+    }
+
+    #[test]
+    fn mismatched_normal_size_is_rejected() {
+        let mut builder = AtlasBuilder::new(1);
+        let result = builder.add("bad", &solid(4, 4, 0), Some(&solid(8, 4, 0)));
+        assert!(matches!(result, Err(AtlasError::NormalSizeMismatch { .. })));
+    }
+
+    #[test]
+    fn mip_levels_are_capped_by_the_padding() {
+        assert_eq!(padded_mip_levels(0), 1);
+        assert_eq!(padded_mip_levels(2), 1);
+        assert_eq!(padded_mip_levels(4), 2);
+        assert_eq!(padded_mip_levels(8), 3);
+    }
+}
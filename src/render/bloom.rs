@@ -0,0 +1,258 @@
+use std::borrow::Cow;
+
+use crevice::std140::{AsStd140, Std140};
+use glam::UVec2;
+use serde::{Deserialize, Serialize};
+use wgpu::{BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferSize, BufferUsages, CommandEncoder, Device, Queue, RenderPipeline, Sampler, SamplerBindingType, ShaderStages, TextureFormat, TextureSampleType, TextureView};
+
+use super::{SamplerSettings, Texture, HDR_FORMAT};
+
+/// Most levels the chain is allocated with, the first at half resolution.
+pub const MAX_BLOOM_LEVELS: u32 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    /// Brightness (largest channel of the HDR color) above which light blooms.
+    pub threshold: f32,
+    /// Width of the soft transition below `threshold`.
+    pub knee: f32,
+    /// How much of the blurred light is added back before tonemapping.
+    pub intensity: f32,
+    /// Levels blurred, up to `MAX_BLOOM_LEVELS`. More spread the glow wider.
+    pub levels: u32
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self { enabled: false, threshold: 1.0, knee: 0.5, intensity: 0.3, levels: 5 }
+    }
+}
+
+#[derive(AsStd140)]
+pub struct GPUBloomSettings {
+    pub threshold: f32,
+    pub knee: f32
+}
+
+/// The textures of an allocated chain and the bind groups reading them.
+struct BloomLevels {
+    textures: Vec<Texture>,
+    /// Reads the HDR target.
+    hdr_bind_group: BindGroup,
+    /// Each reads the level at its index.
+    level_bind_groups: Vec<BindGroup>
+}
+
+/// Blurs the bright parts of the HDR target into a half resolution texture
+/// that the tonemap pass adds back. The chain is only allocated while bloom
+/// is enabled. Like the HDR target it renders to `HDR_FORMAT` with blending
+/// and samples it with filtering, which WebGL2 only allows with the
+/// `EXT_color_buffer_float` and `EXT_float_blend` extensions.
+pub struct BloomPass {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    settings: Buffer,
+    prefilter: RenderPipeline,
+    downsample: RenderPipeline,
+    upsample: RenderPipeline,
+    /// Bound as the output while no chain is allocated.
+    empty: Texture,
+    levels: Option<BloomLevels>,
+    size: UVec2,
+    active_levels: u32
+}
+
+impl BloomPass {
+    pub fn new(device: &Device, source: &str, size: UVec2) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("bloom_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(GPUBloomSettings::std140_size_static() as u64)
+                    },
+                    count: None
+                }
+            ]
+        });
+        let settings = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("bloom_settings_buffer"),
+            size: GPUBloomSettings::std140_size_static() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let sampler = SamplerSettings::trilinear().create_sampler(device);
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("bloom_shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source))
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("bloom_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = |entry_point, blend| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point,
+                targets: &[wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend,
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None
+        });
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add
+        };
+        let prefilter = pipeline("fs_prefilter", None);
+        let downsample = pipeline("fs_downsample", None);
+        let upsample = pipeline("fs_upsample", Some(wgpu::BlendState { color: additive, alpha: additive }));
+
+        let empty = Texture::create_render_target(device, UVec2::ONE, TextureFormat::Rgba8Unorm, "bloom_empty");
+        Self { layout, sampler, settings, prefilter, downsample, upsample, empty, levels: None, size, active_levels: 0 }
+    }
+
+    /// Recreates the chain, if allocated, for an HDR target of `size`.
+    pub fn resize(&mut self, device: &Device, size: UVec2, hdr: &TextureView) {
+        self.size = size;
+        if self.levels.is_some() {
+            self.levels = Some(self.create_levels(device, hdr));
+        }
+    }
+
+    /// The blurred result at half the HDR resolution, or a black texel while
+    /// bloom is disabled.
+    pub fn output(&self) -> &TextureView {
+        self.levels.as_ref().map_or(&self.empty.view, |levels| &levels.textures[0].view)
+    }
+
+    /// Allocates the chain when bloom is enabled and frees it when disabled.
+    /// Returns true if `output` changed, so bind groups reading it must be rebuilt.
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, settings: &BloomSettings, hdr: &TextureView) -> bool {
+        let changed = settings.enabled != self.levels.is_some();
+        if changed {
+            self.levels = settings.enabled.then(|| self.create_levels(device, hdr));
+        }
+        self.active_levels = match self.levels.as_ref() {
+            Some(levels) => settings.levels.clamp(1, levels.textures.len() as u32),
+            None => 0
+        };
+        let gpu_settings = GPUBloomSettings { threshold: settings.threshold, knee: settings.knee };
+        queue.write_buffer(&self.settings, 0, gpu_settings.as_std140().as_bytes());
+        changed
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active_levels > 0
+    }
+
+    /// Runs the chain over the HDR target. Does nothing while disabled.
+    pub fn render(&self, encoder: &mut CommandEncoder) {
+        let (levels, chain) = match self.levels.as_ref() {
+            Some(chain) if self.active_levels > 0 => (self.active_levels as usize, chain),
+            _ => return
+        };
+        let mut pass = |pipeline: &RenderPipeline, bind_group: &BindGroup, target: &TextureView, load| {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("bloom_pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load, store: true }
+                }],
+                depth_stencil_attachment: None
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        };
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        pass(&self.prefilter, &chain.hdr_bind_group, &chain.textures[0].view, clear);
+        for level in 1..levels {
+            pass(&self.downsample, &chain.level_bind_groups[level - 1], &chain.textures[level].view, clear);
+        }
+        for level in (0..levels - 1).rev() {
+            pass(&self.upsample, &chain.level_bind_groups[level + 1], &chain.textures[level].view, wgpu::LoadOp::Load);
+        }
+    }
+
+    fn create_levels(&self, device: &Device, hdr: &TextureView) -> BloomLevels {
+        let bind_group = |view: &TextureView| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bloom_bind_group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.settings.as_entire_binding() }
+            ]
+        });
+        let textures: Vec<Texture> = bloom_level_sizes(self.size).into_iter()
+            .map(|level_size| Texture::create_render_target(device, level_size, HDR_FORMAT, "bloom_level"))
+            .collect();
+        let level_bind_groups = textures.iter().map(|level| bind_group(&level.view)).collect();
+        BloomLevels { textures, hdr_bind_group: bind_group(hdr), level_bind_groups }
+    }
+}
+
+/// Sizes of the chain for an HDR target of `size`, halving down to 1x1 at most.
+pub fn bloom_level_sizes(size: UVec2) -> Vec<UVec2> {
+    let mut sizes = Vec::new();
+    let mut level = (size / 2).max(UVec2::ONE);
+    while sizes.len() < MAX_BLOOM_LEVELS as usize {
+        sizes.push(level);
+        if level == UVec2::ONE {
+            break;
+        }
+        level = (level / 2).max(UVec2::ONE);
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_halve_down_to_one_texel() {
+        assert_eq!(bloom_level_sizes(UVec2::new(1280, 720)), [(640, 360), (320, 180), (160, 90), (80, 45), (40, 22), (20, 11)].map(|(x, y)| UVec2::new(x, y)));
+        assert_eq!(bloom_level_sizes(UVec2::new(8, 2)), [(4, 1), (2, 1), (1, 1)].map(|(x, y)| UVec2::new(x, y)));
+        assert_eq!(bloom_level_sizes(UVec2::new(1, 1)), [UVec2::ONE]);
+    }
+}
@@ -1,13 +1,61 @@
-use glam::{Mat4, Quat};
-use wgpu::{Device, ShaderStages, BufferBindingType, BindingType, BindGroupLayoutEntry, BindGroupDescriptor, BindGroupEntry, util::{BufferInitDescriptor, DeviceExt}, BufferUsages, BindGroupLayout, BindGroup, Buffer, Queue};
+use glam::{vec2, Mat4, UVec2, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
 
-use crate::components::transform::Transform;
+use crate::components::{transform::Transform, EyePosition};
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct CameraUniform {
-    //view_proj: [[f32; 4]; 4],
-    // view_proj: Mat4
+/// How the camera's bounds follow the size of the target it renders to.
+/// Sizes are in world units before zoom.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ResizePolicy {
+    /// Shows this height, widening or narrowing with the aspect ratio.
+    FixedHeight(f32),
+    /// Shows this width, growing or shrinking vertically with the aspect ratio.
+    FixedWidth(f32),
+    /// One world unit covers this many pixels, so a bigger window shows more.
+    PixelPerfect(f32),
+    /// Shows exactly this area at its own aspect ratio, centered, with the
+    /// rest of the target left at the clear color.
+    Letterbox { width: f32, height: f32 }
+}
+
+impl Default for ResizePolicy {
+    fn default() -> Self {
+        ResizePolicy::FixedHeight(2.0)
+    }
+}
+
+impl ResizePolicy {
+    /// Half the visible width and height for a target of `size` pixels.
+    pub fn half_extents(&self, size: UVec2) -> Vec2 {
+        let size = size.max(UVec2::ONE).as_vec2();
+        let aspect = size.x / size.y;
+        let extents = match *self {
+            ResizePolicy::FixedHeight(height) => vec2(height * aspect, height),
+            ResizePolicy::FixedWidth(width) => vec2(width, width / aspect),
+            ResizePolicy::PixelPerfect(pixels_per_unit) => size / pixels_per_unit,
+            // Fit the whole area; the scissor rectangle hides the overhang.
+            ResizePolicy::Letterbox { width, height } if aspect > width / height => vec2(height * aspect, height),
+            ResizePolicy::Letterbox { width, .. } => vec2(width, width / aspect)
+        };
+        extents * 0.5
+    }
+
+    /// The centered pixel rectangle `[x, y, width, height]` that letterboxing
+    /// draws into, or `None` when the whole target is used.
+    pub fn scissor(&self, size: UVec2) -> Option<[u32; 4]> {
+        let (width, height) = match *self {
+            ResizePolicy::Letterbox { width, height } => (width, height),
+            _ => return None
+        };
+        let design_aspect = width / height;
+        let fitted = if size.x as f32 / size.y.max(1) as f32 > design_aspect {
+            UVec2::new((size.y as f32 * design_aspect).round() as u32, size.y)
+        } else {
+            UVec2::new(size.x, (size.x as f32 / design_aspect).round() as u32)
+        }.min(size);
+        let offset = (size - fitted) / 2;
+        Some([offset.x, offset.y, fitted.x, fitted.y])
+    }
 }
 
 pub struct Camera {
@@ -19,22 +67,17 @@ pub struct Camera {
     pub far: f32,
     pub transform: Transform,
     pub zoom: f32,
+    pub resize_policy: ResizePolicy,
+    /// Target size the bounds were last fitted to by `resize`.
+    pub viewport_size: UVec2,
 
     pub proj_matrix: Mat4,
     pub view_matrix: Mat4,
     pub view_proj_matrix: Mat4,
 
-    pub bind_group_layout: Option<BindGroupLayout>,
-    pub bind_group: Option<BindGroup>,
-    pub camera_buffer: Option<Buffer>,
-
     dirty: bool
 }
 
-pub fn degress_to_radians(degrees: f32) -> f32 {
-    degrees * 0.0174533
-}
-
 impl Camera {
     pub fn new(left: f32, right: f32, bottom: f32, top: f32) -> Self {
         let near = 500.0;
@@ -64,26 +107,77 @@ impl Camera {
             proj_matrix,
             view_matrix,
             view_proj_matrix,
-            bind_group_layout: None,
-            bind_group: None,
-            camera_buffer: None,
             zoom,
+            resize_policy: ResizePolicy::FixedHeight(top - bottom),
+            viewport_size: UVec2::ZERO,
             dirty: true
         }
     }
 
+    /// Refits the bounds to a target of `size` pixels using `resize_policy`,
+    /// keeping the view centered. Zero sized (minimized) targets are ignored.
+    pub fn resize(&mut self, size: UVec2) {
+        if size.x == 0 || size.y == 0 {
+            return;
+        }
+        let extents = self.resize_policy.half_extents(size);
+        self.left = -extents.x;
+        self.right = extents.x;
+        self.bottom = -extents.y;
+        self.top = extents.y;
+        self.viewport_size = size;
+        self.dirty = true;
+    }
+
+    /// World position (on the z = 0 plane of the camera's transform) under a
+    /// pixel of the target the camera was last fitted to, with y down as in
+    /// window coordinates.
+    pub fn screen_to_world(&self, pixel: Vec2) -> Vec2 {
+        let uv = pixel / self.viewport_size.max(UVec2::ONE).as_vec2();
+        let local = vec2(
+            self.left + (self.right - self.left) * uv.x,
+            self.top - (self.top - self.bottom) * uv.y
+        ) * self.zoom;
+        self.transform.get_matrix().transform_point3(local.extend(0.0)).truncate()
+    }
+
+    /// Half the visible width and height in world units, zoom included.
+    pub fn half_view_extents(&self) -> Vec2 {
+        vec2(self.right - self.left, self.top - self.bottom) * self.zoom * 0.5
+    }
+
+    /// Flags the matrices for `update` after the transform, zoom or bounds
+    /// were changed directly.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Recomputes the matrices if anything changed since the last call.
+    /// Returns whether they changed and need uploading.
+    pub fn update(&mut self) -> bool {
+        if !self.dirty {
+            return false;
+        }
+        self.set_projection(self.left, self.right, self.bottom, self.top);
+        self.recalculate_view_matrix();
+        self.dirty = false;
+        true
+    }
+
     pub fn recalculate_view_matrix(&mut self) {
         let transform = Mat4::from_scale_rotation_translation(self.transform.scale, self.transform.rotation, self.transform.translation);
         self.view_matrix = transform.inverse();
         self.view_proj_matrix = self.proj_matrix * self.view_matrix;
+        self.dirty = true;
     }
 
     pub fn zoom_in(&mut self, amount: f32) {
         self.zoom += amount;
-        // let aspect = 800.0 / 600.0;
 
-        println!("{}", (self.left * self.zoom) / (self.bottom * self.zoom));
-        
         self.proj_matrix = Mat4::orthographic_rh(
             self.left * self.zoom, 
             self.right * self.zoom, 
@@ -93,64 +187,71 @@ impl Camera {
             self.far
         );
         self.view_proj_matrix = self.proj_matrix * self.view_matrix;
+        self.dirty = true;
     }
 
     pub fn set_projection(&mut self, left: f32, right: f32, bottom: f32, top: f32) {
-        let near = 500.0;
-        let far = -500.0;
-        //let t = &self.transform;
+        self.left = left;
+        self.right = right;
+        self.bottom = bottom;
+        self.top = top;
         self.proj_matrix = Mat4::orthographic_rh(
             left * self.zoom, 
             right * self.zoom, 
             bottom * self.zoom, 
             top * self.zoom, 
-            near, 
-            far
+            self.near, 
+            self.far
         );
         self.view_proj_matrix = self.proj_matrix * self.view_matrix;
+        self.dirty = true;
+    }
+
+    /// The camera looks down its local -Z.
+    pub fn eye_position(&self) -> EyePosition {
+        EyePosition {
+            eye_position: self.transform.translation,
+            view_direction: self.transform.rotation * -Vec3::Z
+        }
     }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(-1.0, 1.0, -1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn build_buffers(&mut self, device: &Device) {
-        // let mut camera_uniform = CameraUniform::new();
-
-        //let test :[[f32; 4]; 4] = self.view_proj_matrix
-        //let test = bytemuck::cast_slice(&[self.view_proj_matrix]);
-
-        self.camera_buffer = Some(device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("camera_buffer"),
-            contents: bytemuck::cast_slice(&[self.view_proj_matrix]),
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
-        }));
-
-        // self.bind_group_layout = Some(device.create_bind_group_layout(
-        //     &wgpu::BindGroupLayoutDescriptor {
-        //         entries: &[
-        //             BindGroupLayoutEntry {
-        //                 binding: 0,
-        //                 visibility: ShaderStages::VERTEX,
-        //                 ty: BindingType::Buffer {
-        //                     ty: BufferBindingType::Uniform,
-        //                     has_dynamic_offset: false,
-        //                     min_binding_size: None
-        //                 },
-        //                 count: None
-        //             }
-        //         ],
-        //         label: Some("camera_bind_group_layout")
-        //     }
-        // ));
-
-        // self.bind_group = Some(device.create_bind_group(
-        //     &BindGroupDescriptor {
-        //         layout: self.bind_group_layout.as_ref().unwrap(),
-        //         entries: &[
-        //             BindGroupEntry {
-        //                 binding: 0,
-        //                 resource: self.camera_buffer.as_ref().unwrap().as_entire_binding()
-        //             },
-        //         ],
-        //         label: Some("camera_bind_group")
-        //     }
-        // ));
-    }
-}
\ No newline at end of file
+    #[test]
+    fn fixed_height_follows_aspect() {
+        let mut camera = Camera { resize_policy: ResizePolicy::FixedHeight(0.72), ..Default::default() };
+        camera.resize(UVec2::new(1280, 720));
+        assert!((camera.right - 0.64).abs() < 1e-6 && (camera.top - 0.36).abs() < 1e-6);
+        camera.resize(UVec2::new(720, 720));
+        assert!((camera.right - 0.36).abs() < 1e-6 && (camera.top - 0.36).abs() < 1e-6);
+    }
+
+    #[test]
+    fn zero_size_keeps_bounds() {
+        let mut camera = Camera::default();
+        camera.resize(UVec2::new(800, 600));
+        assert!(camera.update());
+        camera.resize(UVec2::new(0, 600));
+        assert_eq!(camera.viewport_size, UVec2::new(800, 600));
+        assert!(!camera.update());
+    }
+
+    #[test]
+    fn letterbox_fits_and_centers() {
+        let policy = ResizePolicy::Letterbox { width: 16.0, height: 9.0 };
+        assert_eq!(policy.scissor(UVec2::new(1000, 900)), Some([0, 168, 1000, 563]));
+        assert_eq!(policy.scissor(UVec2::new(2000, 900)), Some([200, 0, 1600, 900]));
+        assert_eq!(policy.half_extents(UVec2::new(2000, 900)), vec2(10.0, 4.5));
+        assert_eq!(ResizePolicy::PixelPerfect(16.0).half_extents(UVec2::new(320, 160)), vec2(10.0, 5.0));
+        assert_eq!(ResizePolicy::FixedWidth(4.0).scissor(UVec2::new(320, 160)), None);
+    }
+}
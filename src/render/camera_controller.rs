@@ -0,0 +1,234 @@
+use glam::{vec2, Vec2};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+
+use super::Camera;
+
+/// Pixels of a touchpad scroll counted as one wheel line.
+const PIXELS_PER_LINE: f32 = 40.0;
+
+/// Rectangle the camera's view is kept inside of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldBounds {
+    pub min: Vec2,
+    pub max: Vec2
+}
+
+/// Drives a `Camera` from window input: dragging pans, the wheel zooms
+/// around the cursor and WASD or the arrow keys move. Alternatively it
+/// follows a target. Only marks the camera dirty when it actually moves, so
+/// the renderer re-uploads the camera only then.
+pub struct CameraController {
+    pub pan_button: MouseButton,
+    /// Zoom factor per wheel line.
+    pub zoom_step: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// Keyboard movement in screen heights per second, so it feels the same
+    /// at any zoom.
+    pub move_speed: f32,
+    /// Position to keep centered. Panning and keyboard movement are ignored
+    /// while it is set.
+    pub follow: Option<Vec2>,
+    /// How quickly the camera catches up with `follow`, per second. Zero or
+    /// less snaps to the target.
+    pub follow_smoothing: f32,
+    pub bounds: Option<WorldBounds>,
+    cursor: Option<Vec2>,
+    dragging: bool,
+    /// Up, down, left, right.
+    held: [bool; 4]
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            pan_button: MouseButton::Left,
+            zoom_step: 1.1,
+            min_zoom: 0.01,
+            max_zoom: 10_000.0,
+            move_speed: 1.0,
+            follow: None,
+            follow_smoothing: 8.0,
+            bounds: None,
+            cursor: None,
+            dragging: false,
+            held: [false; 4]
+        }
+    }
+}
+
+impl CameraController {
+    /// Applies a window event to the camera. Returns whether the event was
+    /// used.
+    pub fn handle_event(&mut self, event: &WindowEvent, camera: &mut Camera) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = vec2(position.x as f32, position.y as f32);
+                if let (true, Some(last)) = (self.dragging, self.cursor) {
+                    self.pan(camera, last, cursor);
+                }
+                self.cursor = Some(cursor);
+                self.dragging
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.dragging = false;
+                false
+            },
+            WindowEvent::MouseInput { state, button, .. } if *button == self.pan_button => {
+                self.dragging = *state == ElementState::Pressed;
+                true
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE
+                };
+                let cursor = self.cursor.unwrap_or_else(|| camera.viewport_size.as_vec2() * 0.5);
+                self.zoom_at(camera, cursor, lines);
+                true
+            },
+            WindowEvent::KeyboardInput { input, .. } => {
+                let direction = match input.virtual_keycode {
+                    Some(VirtualKeyCode::W | VirtualKeyCode::Up) => 0,
+                    Some(VirtualKeyCode::S | VirtualKeyCode::Down) => 1,
+                    Some(VirtualKeyCode::A | VirtualKeyCode::Left) => 2,
+                    Some(VirtualKeyCode::D | VirtualKeyCode::Right) => 3,
+                    _ => return false
+                };
+                self.held[direction] = input.state == ElementState::Pressed;
+                true
+            },
+            WindowEvent::Focused(false) => {
+                self.held = [false; 4];
+                self.dragging = false;
+                false
+            },
+            _ => false
+        }
+    }
+
+    /// Moves the camera so the world point under `from` ends up under `to`.
+    pub fn pan(&self, camera: &mut Camera, from: Vec2, to: Vec2) {
+        if self.follow.is_some() {
+            return;
+        }
+        let offset = camera.screen_to_world(from) - camera.screen_to_world(to);
+        self.move_to(camera, camera.transform.translation.truncate() + offset);
+    }
+
+    /// Zooms by `zoom_step` per line, positive towards the scene, keeping the
+    /// world point under `cursor` in place.
+    pub fn zoom_at(&self, camera: &mut Camera, cursor: Vec2, lines: f32) {
+        let zoom = (camera.zoom * self.zoom_step.powf(-lines)).clamp(self.min_zoom, self.max_zoom);
+        if zoom == camera.zoom {
+            return;
+        }
+        let anchor = camera.screen_to_world(cursor);
+        camera.zoom = zoom;
+        camera.mark_dirty();
+        let offset = anchor - camera.screen_to_world(cursor);
+        self.move_to(camera, camera.transform.translation.truncate() + offset);
+    }
+
+    /// Applies held keys, follows the target and clamps to the bounds.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let position = camera.transform.translation.truncate();
+        let target = match self.follow {
+            Some(target) if self.follow_smoothing > 0.0 => position.lerp(target, 1.0 - (-self.follow_smoothing * dt).exp()),
+            Some(target) => target,
+            None => {
+                let [up, down, left, right] = self.held.map(|held| held as i32 as f32);
+                let direction = vec2(right - left, up - down).normalize_or_zero();
+                position + direction * self.move_speed * camera.half_view_extents().y * 2.0 * dt
+            }
+        };
+        self.move_to(camera, target);
+    }
+
+    /// Sets the camera's position, clamped to `bounds`, marking it dirty only
+    /// if it changed.
+    fn move_to(&self, camera: &mut Camera, position: Vec2) {
+        let position = match self.bounds {
+            Some(bounds) => clamp_view(position, camera.half_view_extents(), bounds),
+            None => position
+        };
+        let translation = &mut camera.transform.translation;
+        if translation.truncate() != position {
+            *translation = position.extend(translation.z);
+            camera.mark_dirty();
+        }
+    }
+}
+
+/// Keeps a view of `half_extents` around `center` inside `bounds`, centering
+/// it on an axis where the bounds are smaller than the view.
+fn clamp_view(center: Vec2, half_extents: Vec2, bounds: WorldBounds) -> Vec2 {
+    let min = bounds.min + half_extents;
+    let max = bounds.max - half_extents;
+    let middle = (bounds.min + bounds.max) * 0.5;
+    vec2(
+        if min.x <= max.x { center.x.clamp(min.x, max.x) } else { middle.x },
+        if min.y <= max.y { center.y.clamp(min.y, max.y) } else { middle.y }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::UVec2;
+
+    fn fitted_camera() -> Camera {
+        let mut camera = Camera::default();
+        camera.resize(UVec2::new(200, 100));
+        camera.update();
+        camera
+    }
+
+    #[test]
+    fn zoom_keeps_cursor_anchored() {
+        let controller = CameraController::default();
+        let mut camera = fitted_camera();
+        let cursor = vec2(30.0, 80.0);
+        let before = camera.screen_to_world(cursor);
+        controller.zoom_at(&mut camera, cursor, 3.0);
+        assert!(camera.zoom < 1.0);
+        assert!(camera.screen_to_world(cursor).abs_diff_eq(before, 1e-5));
+    }
+
+    #[test]
+    fn drag_follows_cursor() {
+        let controller = CameraController::default();
+        let mut camera = fitted_camera();
+        let grabbed = camera.screen_to_world(vec2(50.0, 50.0));
+        controller.pan(&mut camera, vec2(50.0, 50.0), vec2(120.0, 10.0));
+        assert!(camera.screen_to_world(vec2(120.0, 10.0)).abs_diff_eq(grabbed, 1e-5));
+    }
+
+    #[test]
+    fn bounds_clamp_view() {
+        let mut controller = CameraController {
+            bounds: Some(WorldBounds { min: vec2(-5.0, -1.0), max: vec2(5.0, 1.0) }),
+            follow: Some(vec2(100.0, 3.0)),
+            follow_smoothing: 0.0,
+            ..Default::default()
+        };
+        let mut camera = fitted_camera();
+        controller.update(&mut camera, 0.1);
+        assert_eq!(camera.transform.translation.truncate(), vec2(3.0, 0.0));
+    }
+
+    #[test]
+    fn idle_controller_leaves_camera_clean() {
+        let mut controller = CameraController::default();
+        let mut camera = fitted_camera();
+        controller.update(&mut camera, 0.1);
+        assert!(!camera.is_dirty());
+
+        controller.follow = Some(vec2(1.0, 0.0));
+        controller.update(&mut camera, 0.1);
+        assert!(camera.is_dirty());
+        let x = camera.transform.translation.x;
+        assert!(x > 0.0 && x < 1.0);
+    }
+}
@@ -0,0 +1,58 @@
+use crevice::std140::AsStd140;
+
+/// Intermediate values of the fragment shader that can be written out instead
+/// of the lit color. Names match the captures in `debug-images/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugView {
+    #[default]
+    Lit,
+    OriginalNormal,
+    FlippedYNormal,
+    NormalX,
+    NormalY,
+    NormalZ,
+    Direction,
+    MaxDot,
+    MaxDotOriginalY
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 9] = [
+        DebugView::Lit,
+        DebugView::OriginalNormal,
+        DebugView::FlippedYNormal,
+        DebugView::NormalX,
+        DebugView::NormalY,
+        DebugView::NormalZ,
+        DebugView::Direction,
+        DebugView::MaxDot,
+        DebugView::MaxDotOriginalY
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugView::Lit => "albedo_diffuse_attenuation",
+            DebugView::OriginalNormal => "original_normal",
+            DebugView::FlippedYNormal => "flipped_y_normal",
+            DebugView::NormalX => "normal_x_before",
+            DebugView::NormalY => "normal_y_before",
+            DebugView::NormalZ => "normal_z_before",
+            DebugView::Direction => "direction",
+            DebugView::MaxDot => "max_dot",
+            DebugView::MaxDotOriginalY => "max_dot_original_y"
+        }
+    }
+}
+
+#[derive(AsStd140)]
+pub struct GPUDebugSettings {
+    pub view: u32
+}
+
+impl From<DebugView> for GPUDebugSettings {
+    fn from(view: DebugView) -> Self {
+        Self {
+            view: view as u32
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::borrow::Cow;
+
+use crevice::std140::AsStd140;
+use glam::{Mat4, UVec2, Vec2, uvec2, vec2, vec4};
+use wgpu::{Adapter, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BufferBindingType, BufferSize, BufferUsages, ComputePipeline, Device, DownlevelFlags, Limits, ShaderStages};
+
+use crate::components::{GPUSceneLights, GPUPointLight};
+
+use super::specialize;
+
+/// The screen is split into a fixed grid so the tile arrays have a constant
+/// size regardless of resolution; tiles grow with the render target instead.
+pub const TILES_X: u32 = 16;
+pub const TILES_Y: u32 = 9;
+pub const TILE_COUNT: u32 = TILES_X * TILES_Y;
+
+/// A light stops affecting a tile once its contribution drops below this.
+pub const DEFAULT_LIGHT_CUTOFF: f32 = 1.0 / 256.0;
+
+const CULL_WORKGROUP_SIZE: u32 = 8;
+
+/// Where the per-tile light lists are built. Both produce the same buffer
+/// layout: one light count per tile followed by a fixed number of light
+/// index slots per tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightCullingMode {
+    /// A compute pass bins lights on the GPU and the fragment shader reads
+    /// the lists from storage buffers.
+    Compute,
+    /// Lights are binned on the CPU and uploaded as uniforms, for adapters
+    /// without compute shaders or fragment storage buffers (WebGL2).
+    #[default]
+    Cpu
+}
+
+impl LightCullingMode {
+    pub fn for_adapter(adapter: &Adapter) -> Self {
+        let compute = adapter.get_downlevel_properties().flags.contains(DownlevelFlags::COMPUTE_SHADERS);
+        let storage = adapter.limits().max_storage_buffers_per_shader_stage >= 2;
+        if compute && storage {
+            LightCullingMode::Compute
+        } else {
+            LightCullingMode::Cpu
+        }
+    }
+
+    /// Like `for_adapter`, for a device created elsewhere whose limits may be
+    /// lower than the adapter's.
+    pub fn for_device(adapter: &Adapter, device: &Device) -> Self {
+        let compute = adapter.get_downlevel_properties().flags.contains(DownlevelFlags::COMPUTE_SHADERS);
+        if compute && device.limits().max_storage_buffers_per_shader_stage >= 2 {
+            LightCullingMode::Compute
+        } else {
+            LightCullingMode::Cpu
+        }
+    }
+
+    pub fn limits(&self, adapter: &Adapter) -> Limits {
+        let limits = match self {
+            LightCullingMode::Compute => Limits::downlevel_defaults(),
+            LightCullingMode::Cpu => Limits::downlevel_webgl2_defaults()
+        };
+        limits.using_resolution(adapter.limits())
+    }
+
+    /// Asset path of the WGSL declaring `tile_light_count` and `tile_light_index` for `frag.wgsl`.
+    pub fn fragment_shader_path(&self) -> &'static str {
+        match self {
+            LightCullingMode::Compute => "light_tiles_storage.wgsl",
+            LightCullingMode::Cpu => "light_tiles_uniform.wgsl"
+        }
+    }
+
+    pub fn tile_binding_type(&self) -> BufferBindingType {
+        match self {
+            LightCullingMode::Compute => BufferBindingType::Storage { read_only: true },
+            LightCullingMode::Cpu => BufferBindingType::Uniform
+        }
+    }
+
+    pub fn tile_buffer_usage(&self) -> BufferUsages {
+        match self {
+            LightCullingMode::Compute => BufferUsages::STORAGE,
+            LightCullingMode::Cpu => BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        }
+    }
+
+    /// Index slots per tile that fit in one binding of the tile index buffer.
+    pub fn max_lights_per_tile(&self, limits: &Limits, requested: u32) -> u32 {
+        let binding_size = match self {
+            LightCullingMode::Compute => limits.max_storage_buffer_binding_size,
+            LightCullingMode::Cpu => limits.max_uniform_buffer_binding_size
+        };
+        requested.min(binding_size / 4 / TILE_COUNT).max(1)
+    }
+}
+
+#[derive(AsStd140)]
+pub struct GPULightGrid {
+    pub tiles: UVec2,
+    pub tile_size: Vec2,
+    pub target_size: Vec2,
+    pub max_lights_per_tile: u32,
+    pub cutoff: f32
+}
+
+/// Each tile lists at most `max_lights_per_tile` lights. The renderer sizes
+/// the lists to hold every point light, so only the uniform buffers of
+/// `LightCullingMode::Cpu` can run out of slots; lights past a full tile are
+/// then left out of it.
+pub struct LightGrid {
+    pub tiles: UVec2,
+    pub target_size: UVec2,
+    pub max_lights_per_tile: u32,
+    pub cutoff: f32
+}
+
+/// Per-tile light lists built by `LightGrid::cull`.
+pub struct TileLights {
+    pub counts: Vec<u32>,
+    /// `max_lights_per_tile` index slots per tile.
+    pub indices: Vec<u32>,
+    /// Light and tile pairs left out because the tile's list was full.
+    pub dropped: u32
+}
+
+fn align_16(size: u64) -> u64 {
+    size.div_ceil(16) * 16
+}
+
+impl LightGrid {
+    pub fn new(target_size: UVec2, max_lights_per_tile: u32) -> Self {
+        Self {
+            tiles: uvec2(TILES_X, TILES_Y),
+            target_size,
+            max_lights_per_tile,
+            cutoff: DEFAULT_LIGHT_CUTOFF
+        }
+    }
+
+    pub fn tile_size(&self) -> Vec2 {
+        self.target_size.max(UVec2::ONE).as_vec2() / self.tiles.as_vec2()
+    }
+
+    pub fn counts_size(&self) -> u64 {
+        align_16(TILE_COUNT as u64 * 4)
+    }
+
+    pub fn indices_size(&self) -> u64 {
+        align_16(TILE_COUNT as u64 * self.max_lights_per_tile as u64 * 4)
+    }
+
+    /// Constants for the uniform variant of the tile arrays, which are sized in vec4s.
+    pub fn shader_constants(&self) -> [(&'static str, u32); 2] {
+        [
+            ("TILE_COUNT_VEC4", (self.counts_size() / 16) as u32),
+            ("TILE_INDEX_VEC4", (self.indices_size() / 16) as u32)
+        ]
+    }
+
+    pub fn to_gpu(&self) -> GPULightGrid {
+        GPULightGrid {
+            tiles: self.tiles,
+            tile_size: self.tile_size(),
+            target_size: self.target_size.as_vec2(),
+            max_lights_per_tile: self.max_lights_per_tile,
+            cutoff: self.cutoff
+        }
+    }
+
+    /// Pixel-space bounds of the light's attenuation radius, or `None` if the
+    /// light never falls off and touches every tile.
+    pub fn screen_bounds(&self, light: &GPUPointLight, view_proj: Mat4) -> Option<(Vec2, Vec2)> {
+        let radius = light.radius(self.cutoff);
+        if !radius.is_finite() {
+            return None;
+        }
+
+        let target = self.target_size.as_vec2();
+        let mut min = Vec2::splat(f32::MAX);
+        let mut max = Vec2::splat(f32::MIN);
+        for corner in [vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)] {
+            let world = light.position + (corner * radius).extend(0.0);
+            let clip = view_proj * vec4(world.x, world.y, world.z, 1.0);
+            let ndc = vec2(clip.x, clip.y) / clip.w;
+            let pixel = vec2((ndc.x * 0.5 + 0.5) * target.x, (0.5 - ndc.y * 0.5) * target.y);
+            min = min.min(pixel);
+            max = max.max(pixel);
+        }
+        Some((min, max))
+    }
+
+    /// Bins lights into tiles on the CPU. Lights past a full tile are left
+    /// out of it and counted in `dropped`.
+    pub fn cull<'a>(&self, lights: impl Iterator<Item = &'a GPUPointLight>, view_proj: Mat4) -> TileLights {
+        let tile_size = self.tile_size();
+        let max_per_tile = self.max_lights_per_tile as usize;
+        let mut counts = vec![0u32; TILE_COUNT as usize];
+        let mut indices = vec![0u32; TILE_COUNT as usize * max_per_tile];
+        let mut dropped = 0;
+
+        for (index, light) in lights.enumerate() {
+            let (first, last) = match self.screen_bounds(light, view_proj) {
+                Some((min, max)) => {
+                    if max.x < 0.0 || max.y < 0.0 || min.x >= self.target_size.x as f32 || min.y >= self.target_size.y as f32 {
+                        continue;
+                    }
+                    let first = (min / tile_size).max(Vec2::ZERO).as_uvec2();
+                    let last = (max / tile_size).as_uvec2().min(self.tiles - UVec2::ONE);
+                    (first, last)
+                },
+                None => (UVec2::ZERO, self.tiles - UVec2::ONE)
+            };
+
+            for y in first.y..=last.y {
+                for x in first.x..=last.x {
+                    let tile = (y * self.tiles.x + x) as usize;
+                    let count = counts[tile] as usize;
+                    if count < max_per_tile {
+                        indices[tile * max_per_tile + count] = index as u32;
+                        counts[tile] += 1;
+                    } else {
+                        dropped += 1;
+                    }
+                }
+            }
+        }
+
+        TileLights { counts, indices, dropped }
+    }
+
+    pub fn workgroups(&self) -> (u32, u32) {
+        (self.tiles.x.div_ceil(CULL_WORKGROUP_SIZE), self.tiles.y.div_ceil(CULL_WORKGROUP_SIZE))
+    }
+}
+
+fn uniform_entry(binding: u32, min_binding_size: u64) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: BufferSize::new(min_binding_size)
+        },
+        count: None
+    }
+}
+
+fn storage_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: None
+        },
+        count: None
+    }
+}
+
+pub fn create_cull_layout(device: &Device, max_point_lights: u32) -> BindGroupLayout {
+    device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("light_cull_layout"),
+        entries: &[
+            uniform_entry(0, Mat4::std140_size_static() as u64),  // camera view projection
+            uniform_entry(1, max_point_lights as u64 * GPUPointLight::std140_size_static() as u64),  // point lights
+            uniform_entry(2, GPUSceneLights::std140_size_static() as u64),  // light counts
+            uniform_entry(3, GPULightGrid::std140_size_static() as u64),  // tile grid
+            storage_entry(4),  // tile light counts
+            storage_entry(5),  // tile light indices
+        ]
+    })
+}
+
+/// `light_cull.wgsl` specialized for the point light array size.
+pub fn cull_shader_source(source: &str, max_point_lights: u32) -> String {
+    specialize(source, &[("MAX_POINT_LIGHTS", max_point_lights)])
+}
+
+/// Builds the culling pipeline from the source of `light_cull.wgsl`.
+pub fn create_cull_pipeline(device: &Device, layout: &BindGroupLayout, source: &str, max_point_lights: u32) -> ComputePipeline {
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some("light_cull_shader"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Owned(cull_shader_source(source, max_point_lights)))
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("light_cull_pipeline_layout"),
+        bind_group_layouts: &[layout],
+        push_constant_ranges: &[]
+    });
+
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("light_cull_pipeline"),
+        layout: Some(&pipeline_layout),
+        module: &shader,
+        entry_point: "cull"
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::GPUAttenuation;
+
+    #[test]
+    fn lights_past_a_full_tile_are_counted() {
+        let grid = LightGrid::new(uvec2(160, 90), 2);
+        // Without attenuation a light reaches every tile.
+        let lights: Vec<GPUPointLight> = (0..3)
+            .map(|_| GPUPointLight { atten: GPUAttenuation { constant: 1.0, linear: 0.0, exp: 0.0 }, ..Default::default() })
+            .collect();
+        let tiles = grid.cull(lights.iter(), Mat4::IDENTITY);
+        assert!(tiles.counts.iter().all(|count| *count == 2));
+        assert_eq!(tiles.dropped, TILE_COUNT);
+    }
+}
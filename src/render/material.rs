@@ -0,0 +1,382 @@
+use crevice::std140::{AsStd140, Std140};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferSize, BufferUsages, Device, Queue, SamplerBindingType, ShaderStages, TextureSampleType};
+
+use crate::components::{GPUAttenuation, GPUBaseLight, GPUPointLight, GPUSpecularAttributes, Sprite};
+use super::{Assets, GPUNormalMapConvention, Handle, NormalMapConvention, Texture};
+
+/// Index of a material in a `MaterialRegistry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MaterialHandle(pub usize);
+
+/// Everything in the material bind group that isn't a texture, packed into a
+/// single uniform to stay within webgl2's per-stage uniform buffer limit.
+#[derive(AsStd140)]
+pub struct GPUMaterial {
+    pub tint: Vec3,
+    pub specular_power: f32,
+    pub specular_intensity: f32,
+    pub flip_green: u32,
+    pub reconstruct_z: u32,
+    /// Emissive color times intensity, multiplying the emissive map.
+    pub emissive: Vec3
+}
+
+/// Lets a sprite's emission light the sprites around it, through a point
+/// light placed in front of it with the material's emissive color.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmissiveLight {
+    /// Scales the emissive intensity into the light's diffuse intensity.
+    pub intensity: f32,
+    /// How far in front of the sprite the light sits, so it reaches the faces
+    /// of its neighbours rather than grazing them.
+    pub height: f32,
+    pub atten: GPUAttenuation
+}
+
+impl Default for EmissiveLight {
+    fn default() -> Self {
+        Self { intensity: 1.0, height: 0.05, atten: GPUAttenuation::default() }
+    }
+}
+
+impl EmissiveLight {
+    /// The light for a sprite at `position` emitting `color` at `intensity`,
+    /// or `None` when it would add nothing.
+    pub fn point_light(&self, color: Vec3, intensity: f32, position: Vec3) -> Option<GPUPointLight> {
+        let diffuse_intensity = intensity * self.intensity;
+        if color == Vec3::ZERO || diffuse_intensity <= 0.0 {
+            return None;
+        }
+        Some(GPUPointLight {
+            base_light: GPUBaseLight { color, ambient_intensity: 0.0, diffuse_intensity },
+            position: position + Vec3::Z * self.height,
+            atten: self.atten
+        })
+    }
+}
+
+/// Textures and shading parameters for a group of sprites. Textures are
+/// referenced by handle, so several materials can draw from the same atlas.
+pub struct Material {
+    pub albedo: Handle<Texture>,
+    /// Flat when `None`.
+    pub normal: Option<Handle<Texture>>,
+    /// Red scales the specular intensity and green the power. White when `None`.
+    pub specular: Option<Handle<Texture>>,
+    /// Scaled by `emissive_color` and `emissive_intensity`, then added to the
+    /// lit color. White when `None`, so the color alone makes the sprite glow.
+    pub emissive: Option<Handle<Texture>>,
+    /// Black, the default, emits nothing whatever the map.
+    pub emissive_color: Vec3,
+    /// Can go past 1 to glow brighter than the lit scene and feed the bloom.
+    pub emissive_intensity: f32,
+    /// Makes every sprite drawn with the material a point light. `None` only glows.
+    pub emissive_light: Option<EmissiveLight>,
+    /// Multiplies the albedo.
+    pub tint: Vec3,
+    pub specular_attributes: GPUSpecularAttributes
+}
+
+impl Material {
+    pub fn new(albedo: Handle<Texture>) -> Self {
+        Self {
+            albedo,
+            normal: None,
+            specular: None,
+            emissive: None,
+            emissive_color: Vec3::ZERO,
+            emissive_intensity: 1.0,
+            emissive_light: None,
+            tint: Vec3::ONE,
+            specular_attributes: GPUSpecularAttributes::default()
+        }
+    }
+
+    pub fn with_normal(mut self, normal: Handle<Texture>) -> Self {
+        self.normal = Some(normal);
+        self
+    }
+
+    pub fn with_specular(mut self, specular: Handle<Texture>, attributes: GPUSpecularAttributes) -> Self {
+        self.specular = Some(specular);
+        self.specular_attributes = attributes;
+        self
+    }
+
+    pub fn with_specular_attributes(mut self, attributes: GPUSpecularAttributes) -> Self {
+        self.specular_attributes = attributes;
+        self
+    }
+
+    /// Shows the map as is unless an emissive color was already set.
+    pub fn with_emissive(mut self, emissive: Handle<Texture>) -> Self {
+        self.emissive = Some(emissive);
+        if self.emissive_color == Vec3::ZERO {
+            self.emissive_color = Vec3::ONE;
+        }
+        self
+    }
+
+    pub fn with_emissive_color(mut self, color: Vec3, intensity: f32) -> Self {
+        self.emissive_color = color;
+        self.emissive_intensity = intensity;
+        self
+    }
+
+    pub fn with_emissive_light(mut self, light: EmissiveLight) -> Self {
+        self.emissive_light = Some(light);
+        self
+    }
+
+    /// The point light a sprite at `position` casts, if the material emits
+    /// light. Its color is `emissive_color`, ignoring the map.
+    pub fn emissive_point_light(&self, position: Vec3) -> Option<GPUPointLight> {
+        self.emissive_light?.point_light(self.emissive_color, self.emissive_intensity, position)
+    }
+
+    pub fn with_tint(mut self, tint: Vec3) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn to_gpu(&self, textures: &Assets<Texture>) -> GPUMaterial {
+        let convention = self.normal.as_ref()
+            .and_then(|normal| textures.get(normal).normal_convention)
+            .unwrap_or(NormalMapConvention::YUp);
+        let convention = GPUNormalMapConvention::from(convention);
+        GPUMaterial {
+            tint: self.tint,
+            specular_power: self.specular_attributes.power,
+            specular_intensity: self.specular_attributes.intensity,
+            flip_green: convention.flip_green,
+            reconstruct_z: convention.reconstruct_z,
+            emissive: self.emissive_color * self.emissive_intensity
+        }
+    }
+
+    /// With `filtering` the textures are declared filterable and the
+    /// samplers filtering, for linear and mipmapped sampling.
+    pub fn create_bind_group_layout(device: &Device, filtering: bool) -> BindGroupLayout {
+        let texture = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: TextureSampleType::Float { filterable: filtering }
+            },
+            count: None
+        };
+        let sampler = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(if filtering { SamplerBindingType::Filtering } else { SamplerBindingType::NonFiltering }),
+            count: None
+        };
+
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                texture(0),  // albedo
+                texture(1),  // normal
+                sampler(2),  // albedo
+                sampler(3),  // normal
+                BindGroupLayoutEntry {  // material parameters
+                    binding: 4,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(GPUMaterial::std140_size_static() as u64)
+                    },
+                    count: None
+                },
+                texture(5),  // specular/gloss
+                texture(6),  // emissive
+            ],
+            label: Some("material_layout")
+        })
+    }
+}
+
+/// 1x1 textures bound in place of the maps a material leaves out.
+struct FallbackTextures {
+    normal: Texture,
+    specular: Texture,
+    emissive: Texture
+}
+
+impl FallbackTextures {
+    fn new(device: &Device, queue: &Queue) -> Self {
+        Self {
+            normal: Texture::solid_color(device, queue, [128, 128, 255, 255], wgpu::TextureFormat::Rgba8Unorm, "fallback_normal"),
+            specular: Texture::solid_color(device, queue, [255, 255, 255, 255], wgpu::TextureFormat::Rgba8Unorm, "fallback_specular"),
+            emissive: Texture::solid_color(device, queue, [255, 255, 255, 255], wgpu::TextureFormat::Rgba8UnormSrgb, "fallback_emissive")
+        }
+    }
+}
+
+struct MaterialEntry {
+    material: Material,
+    buffer: Option<Buffer>,
+    bind_group: Option<BindGroup>,
+    dirty: bool
+}
+
+/// Owns every material and caches its uniform buffer and bind group, which
+/// are only rebuilt after the material is added or borrowed mutably. Slots of
+/// removed materials are reused by later ones.
+#[derive(Default)]
+pub struct MaterialRegistry {
+    entries: Vec<Option<MaterialEntry>>,
+    free: Vec<usize>,
+    fallback: Option<FallbackTextures>
+}
+
+impl MaterialRegistry {
+    pub fn add(&mut self, material: Material) -> MaterialHandle {
+        let entry = Some(MaterialEntry { material, buffer: None, bind_group: None, dirty: true });
+        match self.free.pop() {
+            Some(index) => {
+                self.entries[index] = entry;
+                MaterialHandle(index)
+            },
+            None => {
+                self.entries.push(entry);
+                MaterialHandle(self.entries.len() - 1)
+            }
+        }
+    }
+
+    /// Drops the material along with its buffer, bind group and texture
+    /// handles. Sprites still using the handle are skipped when drawing.
+    pub fn remove(&mut self, handle: MaterialHandle) -> Option<Material> {
+        let entry = self.entries.get_mut(handle.0)?.take()?;
+        self.free.push(handle.0);
+        Some(entry.material)
+    }
+
+    pub fn get(&self, handle: MaterialHandle) -> Option<&Material> {
+        self.entries.get(handle.0)?.as_ref().map(|entry| &entry.material)
+    }
+
+    /// Marks the material to be re-uploaded by the next `prepare`.
+    pub fn get_mut(&mut self, handle: MaterialHandle) -> Option<&mut Material> {
+        self.entries.get_mut(handle.0)?.as_mut().map(|entry| {
+            entry.dirty = true;
+            &mut entry.material
+        })
+    }
+
+    /// Marks every material using `texture` to have its bind group rebuilt,
+    /// after the texture behind the handle was replaced.
+    pub fn texture_changed(&mut self, texture: &Handle<Texture>) {
+        for entry in self.entries.iter_mut().flatten() {
+            let material = &entry.material;
+            let uses = [Some(&material.albedo), material.normal.as_ref(), material.specular.as_ref(), material.emissive.as_ref()]
+                .contains(&Some(texture));
+            entry.dirty |= uses;
+        }
+    }
+
+    /// Point lights for the sprites whose material has an `emissive_light`.
+    pub fn emissive_lights(&self, sprites: &[Sprite]) -> Vec<GPUPointLight> {
+        sprites.iter()
+            .filter_map(|sprite| self.get(sprite.material)?.emissive_point_light(sprite.transform.translation))
+            .collect()
+    }
+
+    /// Whether any material makes its sprites light sources.
+    pub fn has_emissive_lights(&self) -> bool {
+        self.entries.iter().flatten().any(|entry| entry.material.emissive_light.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Uploads the parameters and rebuilds the bind groups of materials that
+    /// changed since the last call.
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, layout: &BindGroupLayout, textures: &Assets<Texture>) {
+        let fallback = self.fallback.get_or_insert_with(|| FallbackTextures::new(device, queue));
+        let dirty = self.entries.iter_mut().enumerate().filter_map(|(index, entry)| Some((index, entry.as_mut()?))).filter(|(_, entry)| entry.dirty);
+        for (index, entry) in dirty {
+            let buffer = entry.buffer.get_or_insert_with(|| device.create_buffer(&BufferDescriptor {
+                label: Some(&format!("material_buffer_{}", index)),
+                size: GPUMaterial::std140_size_static() as u64,
+                mapped_at_creation: false,
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+            }));
+            let material = &entry.material;
+            queue.write_buffer(buffer, 0, material.to_gpu(textures).as_std140().as_bytes());
+
+            let albedo = textures.get(&material.albedo);
+            let normal = material.normal.as_ref().map_or(&fallback.normal, |normal| textures.get(normal));
+            let specular = material.specular.as_ref().map_or(&fallback.specular, |specular| textures.get(specular));
+            let emissive = material.emissive.as_ref().map_or(&fallback.emissive, |emissive| textures.get(emissive));
+            entry.bind_group = Some(device.create_bind_group(&BindGroupDescriptor {
+                layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&albedo.view)
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&normal.view)
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&albedo.sampler)
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::Sampler(&normal.sampler)
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: buffer.as_entire_binding()
+                    },
+                    BindGroupEntry {
+                        binding: 5,
+                        resource: BindingResource::TextureView(&specular.view)
+                    },
+                    BindGroupEntry {
+                        binding: 6,
+                        resource: BindingResource::TextureView(&emissive.view)
+                    }
+                ],
+                label: Some(&format!("material_bind_group_{}", index))
+            }));
+            entry.dirty = false;
+        }
+    }
+
+    /// The material's bind group as of the last `prepare`, or `None` if the
+    /// handle was removed or never prepared.
+    pub fn bind_group(&self, handle: MaterialHandle) -> Option<&BindGroup> {
+        self.entries.get(handle.0)?.as_ref()?.bind_group.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emissive_light_sits_in_front_of_the_sprite() {
+        let emissive = EmissiveLight { intensity: 0.5, height: 0.1, ..EmissiveLight::default() };
+        let light = emissive.point_light(Vec3::new(1.0, 0.5, 0.0), 4.0, Vec3::new(1.0, 2.0, 0.0)).unwrap();
+        assert_eq!(light.position, Vec3::new(1.0, 2.0, 0.1));
+        assert_eq!(light.base_light.diffuse_intensity, 2.0);
+        assert_eq!(light.base_light.color, Vec3::new(1.0, 0.5, 0.0));
+        assert!(emissive.point_light(Vec3::ZERO, 4.0, Vec3::ZERO).is_none());
+        assert!(emissive.point_light(Vec3::ONE, 0.0, Vec3::ZERO).is_none());
+    }
+}
@@ -0,0 +1,241 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use glam::{UVec2, Vec3, Vec4};
+use image::RgbaImage;
+use wgpu::{BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Device, PipelineLayout, Queue, RenderPipeline, ShaderModule, ShaderStages, TextureFormat, TextureSampleType, TextureUsages};
+
+/// How texels are combined into the next mip level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MipFilter {
+    /// Box filter, in linear space for sRGB formats.
+    Color,
+    /// Averages the decoded normals and renormalizes them, so every level
+    /// stays unit length. Two-channel maps have Z reconstructed first.
+    Normal { two_channel: bool }
+}
+
+impl MipFilter {
+    fn entry_point(&self) -> &'static str {
+        match self {
+            MipFilter::Color => "fs_color",
+            MipFilter::Normal { two_channel: false } => "fs_normal",
+            MipFilter::Normal { two_channel: true } => "fs_normal_two_channel"
+        }
+    }
+}
+
+/// Levels in a full chain down to 1x1.
+pub fn mip_level_count(size: UVec2) -> u32 {
+    32 - size.x.max(size.y).max(1).leading_zeros()
+}
+
+/// Builds mip chains, on the GPU with `res/mipmap.wgsl` where the format can
+/// be rendered to and on the CPU otherwise.
+pub struct MipmapGenerator {
+    gpu: Option<GpuMipmaps>
+}
+
+struct GpuMipmaps {
+    layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    shader: ShaderModule,
+    pipelines: HashMap<(TextureFormat, MipFilter), RenderPipeline>
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &Device, source: &str) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("mipmap_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: false }
+                    },
+                    count: None
+                }
+            ]
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("mipmap_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[]
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("mipmap_shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source))
+        });
+        Self { gpu: Some(GpuMipmaps { layout, pipeline_layout, shader, pipelines: HashMap::new() }) }
+    }
+
+    /// Generates every chain on the CPU.
+    pub fn cpu() -> Self {
+        Self { gpu: None }
+    }
+
+    /// Whether chains for `format` are rendered on the GPU. Such textures
+    /// need `RENDER_ATTACHMENT` usage.
+    pub fn renders(&self, format: TextureFormat) -> bool {
+        self.gpu.is_some() && format.describe().guaranteed_format_features.allowed_usages.contains(TextureUsages::RENDER_ATTACHMENT)
+    }
+
+    /// Fills levels `1..level_count` of `texture` from level 0, which must
+    /// already be uploaded. Only call when `renders(format)`.
+    pub fn generate(&mut self, device: &Device, queue: &Queue, texture: &wgpu::Texture, format: TextureFormat, level_count: u32, filter: MipFilter) {
+        let gpu = self.gpu.as_mut().expect("GPU mipmaps need MipmapGenerator::new");
+        let pipeline = gpu.pipelines.entry((format, filter)).or_insert_with(|| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("mipmap_pipeline"),
+            layout: Some(&gpu.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &gpu.shader,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &gpu.shader,
+                entry_point: filter.entry_point(),
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None
+        }));
+
+        let level_view = |level| texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("mip_level_view"),
+            base_mip_level: level,
+            mip_level_count: std::num::NonZeroU32::new(1),
+            ..Default::default()
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("mipmap_encoder") });
+        for level in 1..level_count {
+            let source = level_view(level - 1);
+            let target = level_view(level);
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("mipmap_bind_group"),
+                layout: &gpu.layout,
+                entries: &[wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&source) }]
+            });
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("mipmap_pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: true }
+                }],
+                depth_stencil_attachment: None
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        queue.submit(core::iter::once(encoder.finish()));
+    }
+}
+
+/// The CPU chain below `image`, from half size down to 1x1.
+pub fn cpu_mip_chain(image: &RgbaImage, filter: MipFilter, srgb: bool) -> Vec<RgbaImage> {
+    let mut levels: Vec<RgbaImage> = Vec::new();
+    while levels.last().unwrap_or(image).dimensions() != (1, 1) {
+        let next = downsample(levels.last().unwrap_or(image), filter, srgb);
+        levels.push(next);
+    }
+    levels
+}
+
+/// Halves `image`, matching `res/mipmap.wgsl`.
+pub fn downsample(image: &RgbaImage, filter: MipFilter, srgb: bool) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let texels = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+            let texel = image.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1));
+            Vec4::new(texel[0] as f32, texel[1] as f32, texel[2] as f32, texel[3] as f32) / 255.0
+        });
+        let average = match filter {
+            MipFilter::Color if srgb => {
+                let linear = texels.iter().map(|texel| srgb_to_linear(texel.truncate()).extend(texel.w)).fold(Vec4::ZERO, |sum, texel| sum + texel) * 0.25;
+                linear_to_srgb(linear.truncate()).extend(linear.w)
+            },
+            MipFilter::Color => texels.iter().copied().fold(Vec4::ZERO, |sum, texel| sum + texel) * 0.25,
+            MipFilter::Normal { two_channel } => {
+                let normal = texels.iter()
+                    .map(|texel| decode_normal(*texel, two_channel))
+                    .fold(Vec3::ZERO, |sum, normal| sum + normal)
+                    .try_normalize()
+                    .unwrap_or(Vec3::Z);
+                let alpha = if two_channel { 1.0 } else { texels.iter().map(|texel| texel.w).sum::<f32>() * 0.25 };
+                (normal * 0.5 + 0.5).extend(alpha)
+            }
+        };
+        let texel = (average.clamp(Vec4::ZERO, Vec4::ONE) * 255.0).round();
+        image::Rgba([texel.x as u8, texel.y as u8, texel.z as u8, texel.w as u8])
+    })
+}
+
+fn decode_normal(texel: Vec4, two_channel: bool) -> Vec3 {
+    if two_channel {
+        let x = texel.x * 2.0 - 1.0;
+        let y = texel.y * 2.0 - 1.0;
+        Vec3::new(x, y, (1.0 - x * x - y * y).max(0.0).sqrt())
+    } else {
+        (texel.truncate() * 2.0 - 1.0).normalize_or_zero()
+    }
+}
+
+fn srgb_to_linear(color: Vec3) -> Vec3 {
+    let channel = |c: f32| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+    Vec3::new(channel(color.x), channel(color.y), channel(color.z))
+}
+
+fn linear_to_srgb(color: Vec3) -> Vec3 {
+    let channel = |c: f32| if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    Vec3::new(channel(color.x), channel(color.y), channel(color.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_reaches_one_texel() {
+        assert_eq!(mip_level_count(UVec2::new(1, 1)), 1);
+        assert_eq!(mip_level_count(UVec2::new(256, 64)), 9);
+        assert_eq!(mip_level_count(UVec2::new(300, 5)), 9);
+
+        let chain = cpu_mip_chain(&RgbaImage::new(300, 5), MipFilter::Color, true);
+        assert_eq!(chain.len(), 8);
+        assert_eq!(chain[0].dimensions(), (150, 2));
+        assert_eq!(chain.last().unwrap().dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn srgb_averages_in_linear_space() {
+        let image = RgbaImage::from_fn(2, 1, |x, _| if x == 0 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) });
+        assert_eq!(downsample(&image, MipFilter::Color, false).get_pixel(0, 0)[0], 128);
+        assert_eq!(downsample(&image, MipFilter::Color, true).get_pixel(0, 0)[0], 188);
+    }
+
+    #[test]
+    fn normals_stay_unit_length() {
+        // Two normals tilted 45 degrees in opposite directions average to straight up.
+        let tilt = (0.5f32.sqrt() * 0.5 + 0.5) * 255.0;
+        let image = RgbaImage::from_fn(2, 2, |x, _| {
+            let red = if x == 0 { 255.0 - tilt } else { tilt };
+            image::Rgba([red.round() as u8, 128, tilt.round() as u8, 255])
+        });
+        for two_channel in [false, true] {
+            let texel = downsample(&image, MipFilter::Normal { two_channel }, false).get_pixel(0, 0).0;
+            let normal = decode_normal(Vec4::new(texel[0] as f32, texel[1] as f32, texel[2] as f32, 255.0) / 255.0, two_channel);
+            assert!(normal.abs_diff_eq(Vec3::Z, 0.02), "{:?}", normal);
+            assert!((normal.length() - 1.0).abs() < 0.02);
+        }
+    }
+}
@@ -1,7 +1,31 @@
+pub mod assets;
+pub mod atlas;
+pub mod bloom;
 pub mod camera;
+pub mod camera_controller;
+pub mod debug;
+pub mod light_culling;
+pub mod material;
+pub mod mipmap;
+pub mod sampler;
+pub mod shader;
+pub mod sprite_batch;
 pub mod texture;
+pub mod tonemap;
 pub mod vertex;
 
+pub use assets::*;
+pub use atlas::*;
+pub use bloom::*;
 pub use camera::*;
+pub use camera_controller::*;
+pub use debug::*;
+pub use light_culling::*;
+pub use material::*;
+pub use mipmap::*;
+pub use sampler::*;
+pub use shader::*;
+pub use sprite_batch::*;
 pub use texture::*;
+pub use tonemap::*;
 pub use vertex::*;
\ No newline at end of file
@@ -0,0 +1,143 @@
+use std::{collections::HashMap, num::NonZeroU8, sync::Arc};
+
+use wgpu::{AddressMode, Device, FilterMode, Sampler};
+
+/// How a texture is sampled. Filtering settings need a renderer built with
+/// `RendererBuilder::with_filtering(true)`, whose material layout accepts
+/// filtering samplers; `AssetManager` falls back to nearest without it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerSettings {
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    /// Maximum anisotropy, 1 to turn it off. Rounded down to a power of two
+    /// up to 16, only applied when every filter is linear, and ignored by
+    /// adapters without anisotropic filtering.
+    pub anisotropy: u8
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self::pixel_art()
+    }
+}
+
+impl SamplerSettings {
+    /// Nearest filtering at every level, clamped, so texels stay crisp squares.
+    pub const fn pixel_art() -> Self {
+        Self {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            anisotropy: 1
+        }
+    }
+
+    /// Linear filtering within and between mip levels, clamped.
+    pub const fn trilinear() -> Self {
+        Self {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Self::pixel_art()
+        }
+    }
+
+    pub const fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self
+    }
+
+    /// Tiles the texture, as for floors and backgrounds.
+    pub const fn repeat(self) -> Self {
+        self.with_address_mode(AddressMode::Repeat)
+    }
+
+    pub const fn with_anisotropy(mut self, anisotropy: u8) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    /// Whether any filter is linear, which needs a filtering sampler binding.
+    pub fn is_filtering(&self) -> bool {
+        [self.mag_filter, self.min_filter, self.mipmap_filter].contains(&FilterMode::Linear)
+    }
+
+    /// Nearest filtering with the same address modes, for a non-filtering layout.
+    pub const fn without_filtering(self) -> Self {
+        Self {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            ..self
+        }
+    }
+
+    fn anisotropy_clamp(&self) -> Option<NonZeroU8> {
+        let linear = [self.mag_filter, self.min_filter, self.mipmap_filter].iter().all(|filter| *filter == FilterMode::Linear);
+        let clamp = 1u8 << self.anisotropy.clamp(1, 16).ilog2();
+        NonZeroU8::new(clamp).filter(|clamp| linear && clamp.get() > 1)
+    }
+
+    pub fn create_sampler(&self, device: &Device) -> Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("texture_sampler"),
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: self.anisotropy_clamp(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Shares one `wgpu::Sampler` between all textures with the same settings.
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerSettings, Arc<Sampler>>
+}
+
+impl SamplerCache {
+    pub fn get(&mut self, device: &Device, settings: SamplerSettings) -> Arc<Sampler> {
+        self.samplers.entry(settings).or_insert_with(|| Arc::new(settings.create_sampler(device))).clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anisotropy_needs_linear_filters() {
+        assert_eq!(SamplerSettings::pixel_art().with_anisotropy(16).anisotropy_clamp(), None);
+        assert_eq!(SamplerSettings::trilinear().anisotropy_clamp(), None);
+        assert_eq!(SamplerSettings::trilinear().with_anisotropy(12).anisotropy_clamp(), NonZeroU8::new(8));
+        assert_eq!(SamplerSettings::trilinear().with_anisotropy(200).anisotropy_clamp(), NonZeroU8::new(16));
+    }
+
+    #[test]
+    fn presets() {
+        assert!(!SamplerSettings::default().is_filtering());
+        assert!(SamplerSettings::trilinear().is_filtering());
+        let tiled = SamplerSettings::pixel_art().repeat();
+        assert_eq!((tiled.address_mode_u, tiled.address_mode_v), (AddressMode::Repeat, AddressMode::Repeat));
+        assert_ne!(tiled, SamplerSettings::pixel_art());
+        assert_eq!(SamplerSettings::trilinear().repeat().without_filtering(), tiled);
+    }
+}
@@ -0,0 +1,155 @@
+/// Overrides module-scope `let NAME: u32 = ...;` constants in WGSL source.
+/// WGSL has no pipeline-overridable constants yet, so array sizes that depend
+/// on device limits are patched into the source before compilation.
+pub fn specialize(source: &str, constants: &[(&str, u32)]) -> String {
+    source.lines()
+        .map(|line| {
+            let constant = constants.iter().find(|(name, _)| {
+                line.trim_start()
+                    .strip_prefix("let ")
+                    .and_then(|rest| rest.strip_prefix(name))
+                    .is_some_and(|rest| rest.trim_start().starts_with(':'))
+            });
+            match constant {
+                Some((name, value)) => format!("let {}: u32 = {}u;", name, value),
+                None => line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Where in which source file a shader failed to compile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Parses and validates WGSL with naga before it reaches wgpu, which would
+/// otherwise treat a bad shader as a fatal device error. `source` is `files`
+/// (name and source pairs) joined with newlines, possibly specialized, and
+/// errors are reported against the file and line they came from.
+pub fn validate_wgsl(files: &[(&str, &str)], source: &str) -> Result<(), ShaderError> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|error| {
+        let (line, column) = error.location(source);
+        locate(files, line, column, error.to_string())
+    })?;
+
+    let mut validator = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all());
+    validator.validate(&module).map(|_| ()).map_err(|error| {
+        let mut message = error.to_string();
+        let mut cause = std::error::Error::source(&error);
+        while let Some(inner) = cause {
+            message = format!("{}: {}", message, inner);
+            cause = inner.source();
+        }
+        // Spans run from the enclosing function to the offending expression.
+        let offset = error.spans()
+            .filter_map(|(span, _)| span.to_range())
+            .last()
+            .map_or(0, |range| range.start);
+        let before = &source[..offset.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = offset - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+        locate(files, line, column, message)
+    })
+}
+
+/// Maps a line of the joined source back to the file it came from.
+fn locate(files: &[(&str, &str)], line: usize, column: usize, message: String) -> ShaderError {
+    let mut start = 1;
+    for (file, source) in files {
+        let lines = source.split('\n').count();
+        if line < start + lines {
+            return ShaderError { file: file.to_string(), line: line - start + 1, column, message };
+        }
+        start += lines;
+    }
+    let file = files.last().map_or("wgsl", |(file, _)| file);
+    ShaderError { file: file.to_string(), line, column, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(files: &[(&str, &str)]) -> Result<(), ShaderError> {
+        let source = files.iter().map(|(_, source)| *source).collect::<Vec<_>>().join("\n");
+        validate_wgsl(files, &source)
+    }
+
+    #[test]
+    fn errors_point_at_the_file_they_came_from() {
+        let header = "let A: u32 = 1u;\n\nlet B: u32 = 2u;";
+        let body = "fn f() -> u32 {\n    return A + B +;\n}";
+        let error = validate(&[("header.wgsl", header), ("body.wgsl", body)]).unwrap_err();
+        assert_eq!(error.file, "body.wgsl");
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn validation_errors_have_locations() {
+        let body = "fn f() -> f32 {\n    return 1u;\n}";
+        let error = validate(&[("body.wgsl", body)]).unwrap_err();
+        assert_eq!(error.file, "body.wgsl");
+        assert_eq!(error.line, 2, "{}", error);
+    }
+
+    #[test]
+    fn bundled_shaders_validate() {
+        let read = |name: &str| super::super::builtin_shader(name).unwrap();
+        let frag = read("frag.wgsl");
+        for tiles in ["light_tiles_uniform.wgsl", "light_tiles_storage.wgsl"] {
+            let files = [(tiles, read(tiles)), ("frag.wgsl", frag)];
+            validate(&files).unwrap_or_else(|error| panic!("{}", error));
+        }
+        for name in ["vertex.wgsl", "light_cull.wgsl", "mipmap.wgsl", "tonemap.wgsl", "bloom.wgsl"] {
+            validate(&[(name, read(name))]).unwrap_or_else(|error| panic!("{}", error));
+        }
+    }
+
+    #[test]
+    fn point_light_layout_matches_std140() {
+        use crevice::std140::{AsStd140, Std140};
+        use crate::components::{GPUAttenuation, GPUPointLight};
+
+        // Finds each field in the std140 bytes by a value no other field has.
+        let light = GPUPointLight {
+            position: glam::vec3(7.0, 8.0, 9.0),
+            atten: GPUAttenuation { constant: 11.0, linear: 12.0, exp: 13.0 },
+            ..Default::default()
+        };
+        let std140 = light.as_std140();
+        let bytes = std140.as_bytes();
+        let offset = |value: f32| bytes.chunks(4).position(|chunk| chunk == value.to_le_bytes()).unwrap() as u32 * 4;
+        let position = offset(7.0);
+        let atten = offset(11.0);
+
+        let read = |name: &str| super::super::builtin_shader(name).unwrap();
+        let frag = [read("light_tiles_uniform.wgsl"), read("frag.wgsl")].join("\n");
+        for source in [frag.as_str(), read("light_cull.wgsl")] {
+            let module = naga::front::wgsl::parse_str(source).unwrap();
+            let (members, span) = module.types.iter()
+                .find_map(|(_, ty)| match &ty.inner {
+                    naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some("PointLight") => Some((members, *span)),
+                    _ => None
+                })
+                .unwrap();
+            let member = |name: &str| members.iter().find(|member| member.name.as_deref() == Some(name)).unwrap().offset;
+            assert_eq!(member("position"), position);
+            assert_eq!(member("atten"), atten);
+            assert_eq!(span as usize, GPUPointLight::std140_size_static());
+        }
+    }
+}
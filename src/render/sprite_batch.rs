@@ -0,0 +1,200 @@
+use std::ops::Range;
+
+use glam::{Mat4, uvec2};
+use wgpu::{BindGroup, Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, IndexFormat, Queue, RenderPass};
+
+use crate::components::{GPUTransform, Quad, Sprite};
+use super::MaterialHandle;
+
+/// Per-instance vertex data. Mirrors `GPUTransform`, with the sprite's size
+/// folded into the model matrix so every sprite can share one unit quad, plus
+/// the atlas region the quad's UVs are remapped to.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SpriteInstance {
+    pub model: [[f32; 4]; 4],
+    pub normal_matrix: [[f32; 3]; 3],
+    pub handedness: f32,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2]
+}
+
+impl SpriteInstance {
+    pub const SIZE: BufferAddress = std::mem::size_of::<Self>() as BufferAddress;
+    pub const DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: Self::SIZE,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x3,
+            9 => Float32x3,
+            10 => Float32x3,
+            11 => Float32,
+            12 => Float32x4,
+        ]
+    };
+}
+
+impl From<&Sprite> for SpriteInstance {
+    fn from(sprite: &Sprite) -> Self {
+        let size = sprite.mesh.size.as_vec2();
+        let model = sprite.transform.get_matrix() * Mat4::from_scale(size.extend(1.0));
+        let gpu = GPUTransform::from_matrix(model);
+        let (uv_min, uv_max) = sprite.uv_rect();
+        Self {
+            model: gpu.model.to_cols_array_2d(),
+            normal_matrix: gpu.normal_matrix.to_cols_array_2d(),
+            handedness: gpu.handedness,
+            uv_min: uv_min.to_array(),
+            uv_max: uv_max.to_array()
+        }
+    }
+}
+
+/// A run of instances drawn with one material bind group and one draw call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteBatch {
+    pub material: MaterialHandle,
+    pub instances: Range<u32>
+}
+
+/// Collects sprites into an instance buffer each frame. Sprites are ordered
+/// back to front by translation.z, then by material, and consecutive sprites
+/// sharing a material are merged into one instanced draw.
+pub struct SpriteBatcher {
+    pub quad: Quad,
+    pub instance_buffer: Option<Buffer>,
+    pub capacity: usize,
+    pub batches: Vec<SpriteBatch>,
+    instances: Vec<SpriteInstance>,
+    order: Vec<usize>
+}
+
+impl Default for SpriteBatcher {
+    fn default() -> Self {
+        Self {
+            quad: Quad::from(uvec2(1, 1)),
+            instance_buffer: None,
+            capacity: 0,
+            batches: Vec::new(),
+            instances: Vec::new(),
+            order: Vec::new()
+        }
+    }
+}
+
+impl SpriteBatcher {
+    pub fn build_buffers(&mut self, device: &Device) {
+        self.quad.build_buffers(device);
+    }
+
+    /// Sorts the sprites into batches without touching the GPU.
+    pub fn batch(&mut self, sprites: &[Sprite]) {
+        self.order.clear();
+        self.order.extend(0..sprites.len());
+        self.order.sort_by(|&a, &b| {
+            let (a, b) = (&sprites[a], &sprites[b]);
+            a.transform.translation.z.total_cmp(&b.transform.translation.z)
+                .then(a.material.cmp(&b.material))
+        });
+
+        self.instances.clear();
+        self.batches.clear();
+        for &index in self.order.iter() {
+            let sprite = &sprites[index];
+            let instance = self.instances.len() as u32;
+            self.instances.push(SpriteInstance::from(sprite));
+            match self.batches.last_mut() {
+                Some(batch) if batch.material == sprite.material => batch.instances.end = instance + 1,
+                _ => self.batches.push(SpriteBatch { material: sprite.material, instances: instance..instance + 1 })
+            }
+        }
+    }
+
+    /// Batches the sprites and uploads their instances, growing the instance
+    /// buffer to the next power of two when they no longer fit.
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, sprites: &[Sprite]) {
+        self.batch(sprites);
+
+        if self.instance_buffer.is_none() || self.instances.len() > self.capacity {
+            self.capacity = self.instances.len().next_power_of_two().max(64);
+            self.instance_buffer = Some(device.create_buffer(&BufferDescriptor {
+                label: Some("sprite_instance_buffer"),
+                size: self.capacity as BufferAddress * SpriteInstance::SIZE,
+                mapped_at_creation: false,
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST
+            }));
+        }
+
+        if !self.instances.is_empty() {
+            queue.write_buffer(self.instance_buffer.as_ref().unwrap(), 0, bytemuck::cast_slice(&self.instances));
+        }
+    }
+
+    /// Draws every batch, binding each batch's material at `material_group`.
+    /// Batches whose material has no bind group are skipped.
+    pub fn draw<'a>(&'a self, pass: &mut RenderPass<'a>, material_group: u32, material_bind_group: impl Fn(MaterialHandle) -> Option<&'a BindGroup>) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        pass.set_vertex_buffer(0, self.quad.vertex_buffer.as_ref().unwrap().slice(..));
+        pass.set_vertex_buffer(1, self.instance_buffer.as_ref().unwrap().slice(..));
+        pass.set_index_buffer(self.quad.index_buffer.as_ref().unwrap().slice(..), IndexFormat::Uint16);
+
+        let mut bound = None;
+        for batch in self.batches.iter() {
+            if bound != Some(batch.material) {
+                let bind_group = match material_bind_group(batch.material) {
+                    Some(bind_group) => bind_group,
+                    None => continue
+                };
+                pass.set_bind_group(material_group, bind_group, &[]);
+                bound = Some(batch.material);
+            }
+            pass.draw_indexed(0..Quad::INDICES.len() as u32, 0, batch.instances.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+    use crate::components::Transform;
+
+    fn sprite(material: usize, z: f32) -> Sprite {
+        Sprite {
+            material: MaterialHandle(material),
+            transform: Transform::from_xyz(0.0, 0.0, z),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merges_sprites_by_layer_then_material() {
+        let sprites = [sprite(1, 0.0), sprite(0, 0.0), sprite(1, 0.0), sprite(0, 1.0), sprite(0, 1.0)];
+        let mut batcher = SpriteBatcher::default();
+        batcher.batch(&sprites);
+        assert_eq!(batcher.batches, vec![
+            SpriteBatch { material: MaterialHandle(0), instances: 0..1 },
+            SpriteBatch { material: MaterialHandle(1), instances: 1..3 },
+            SpriteBatch { material: MaterialHandle(0), instances: 3..5 }
+        ]);
+    }
+
+    #[test]
+    fn instance_scales_unit_quad_to_sprite_size() {
+        let mut sprite = sprite(0, 0.0);
+        sprite.transform.scale = vec3(2.0, 1.0, 1.0);
+        sprite.mesh = Quad::from(uvec2(10, 20));
+        let instance = SpriteInstance::from(&sprite);
+        assert_eq!(instance.model[0][0], 20.0);
+        assert_eq!(instance.model[1][1], 20.0);
+        assert_eq!(instance.handedness, 1.0);
+    }
+}
@@ -1,17 +1,126 @@
-use std::num::NonZeroU32;
+use std::{num::NonZeroU32, sync::Arc};
 
-use glam::{Vec2, vec2, UVec2, uvec2};
-use image::GenericImageView;
+use crevice::std140::AsStd140;
+use glam::{UVec2, uvec2};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
 use wgpu::{
-    BindGroupLayout,
-    BindGroup, Device, Queue
+    Device, Queue
 };
 
+use super::{cpu_mip_chain, mip_level_count, MipFilter, MipmapGenerator, SamplerCache, SamplerSettings};
+
+/// How a normal map encodes its tangent-space normal. Tools disagree on the
+/// direction of the green channel, and two-channel maps (BC5/RG) drop blue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalMapConvention {
+    /// OpenGL style (Blender, Substance "OpenGL"): green points up the texture.
+    YUp,
+    /// DirectX style (Unreal, 3ds Max): green points down the texture.
+    YDown,
+    /// Only red and green are stored and Z is reconstructed in the shader.
+    TwoChannel { y_down: bool }
+}
+
+impl NormalMapConvention {
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            NormalMapConvention::TwoChannel { .. } => wgpu::TextureFormat::Rg8Unorm,
+            _ => wgpu::TextureFormat::Rgba8Unorm
+        }
+    }
+
+    pub fn y_down(&self) -> bool {
+        matches!(self, NormalMapConvention::YDown | NormalMapConvention::TwoChannel { y_down: true })
+    }
+
+    /// Re-encodes `image` from this convention into `target`'s. Two-channel
+    /// sources get Z rebuilt into blue, so the result suits any target.
+    pub fn convert(&self, image: &RgbaImage, target: NormalMapConvention) -> RgbaImage {
+        let flip = self.y_down() != target.y_down();
+        let two_channel = matches!(self, NormalMapConvention::TwoChannel { .. });
+        let mut converted = image.clone();
+        if !flip && !two_channel {
+            return converted;
+        }
+        for pixel in converted.pixels_mut() {
+            if two_channel {
+                let x = pixel[0] as f32 / 255.0 * 2.0 - 1.0;
+                let y = pixel[1] as f32 / 255.0 * 2.0 - 1.0;
+                let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+                pixel[2] = ((z * 0.5 + 0.5) * 255.0).round() as u8;
+            }
+            if flip {
+                pixel[1] = 255 - pixel[1];
+            }
+        }
+        converted
+    }
+}
+
+#[derive(AsStd140)]
+pub struct GPUNormalMapConvention {
+    pub flip_green: u32,
+    pub reconstruct_z: u32
+}
+
+impl From<NormalMapConvention> for GPUNormalMapConvention {
+    fn from(convention: NormalMapConvention) -> Self {
+        let (flip_green, reconstruct_z) = match convention {
+            NormalMapConvention::YUp => (false, false),
+            NormalMapConvention::YDown => (true, false),
+            NormalMapConvention::TwoChannel { y_down } => (y_down, true)
+        };
+        Self {
+            flip_green: flip_green as u32,
+            reconstruct_z: reconstruct_z as u32
+        }
+    }
+}
+
+/// How `Texture::from_bytes` and `from_image` store and sample an image.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextureOptions {
+    /// Set for normal maps, which are stored linearly instead of as sRGB.
+    pub normal_convention: Option<NormalMapConvention>,
+    pub sampler: SamplerSettings,
+    /// Build a full mip chain, rendered by the generator when one is passed
+    /// in and built on the CPU otherwise.
+    pub mipmaps: bool,
+    /// Caps the chain at this many levels, such as for an atlas whose padding
+    /// only keeps the first few levels from bleeding.
+    pub max_mip_levels: Option<u32>
+}
+
+impl TextureOptions {
+    pub fn normal(convention: NormalMapConvention) -> Self {
+        Self { normal_convention: Some(convention), ..Default::default() }
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerSettings) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    pub fn with_max_mip_levels(mut self, levels: u32) -> Self {
+        self.max_mip_levels = Some(levels);
+        self
+    }
+}
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
-    pub size: UVec2
+    /// Shared with every texture using the same `SamplerSettings`.
+    pub sampler: Arc<wgpu::Sampler>,
+    pub size: UVec2,
+    /// Set for normal maps, which are stored linearly instead of as sRGB.
+    pub normal_convention: Option<NormalMapConvention>
 }
 
 impl Texture {
@@ -20,10 +129,12 @@ impl Texture {
         queue: &Queue,
         bytes: &[u8],
         label: &str,
-        is_normal_map: bool
-    ) -> Self {
-        let img = image::load_from_memory(bytes).unwrap();
-        Self::from_image(device, queue, &img, label, is_normal_map)
+        options: &TextureOptions,
+        samplers: &mut SamplerCache,
+        mipmaps: Option<&mut MipmapGenerator>
+    ) -> Result<Self, image::ImageError> {
+        let img = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(device, queue, &img, label, options, samplers, mipmaps))
     }
 
     pub fn from_image(
@@ -31,62 +142,232 @@ impl Texture {
         queue: &Queue,
         img: &image::DynamicImage,
         label: &str,
-        is_normal_map: bool,
+        options: &TextureOptions,
+        samplers: &mut SamplerCache,
+        mipmaps: Option<&mut MipmapGenerator>
     ) -> Self {
-        let dimensions = img.dimensions();
-        let rgba = img.to_rgba8();
-        let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1
+        let format = match options.normal_convention {
+            Some(convention) => convention.format(),
+            None => wgpu::TextureFormat::Rgba8UnormSrgb
+        };
+        let (texture, view) = Self::upload(device, queue, &img.to_rgba8(), format, label, options, mipmaps);
+        Self {
+            size: uvec2(img.width(), img.height()),
+            texture,
+            view,
+            sampler: samplers.get(device, options.sampler),
+            normal_convention: options.normal_convention
+        }
+    }
+
+    /// Loads a texture holding data rather than color, such as a specular or
+    /// gloss mask, so it is stored linearly instead of as sRGB. The normal
+    /// convention in `options` is ignored.
+    pub fn data_from_bytes(
+        device: &Device,
+        queue: &Queue,
+        bytes: &[u8],
+        label: &str,
+        options: &TextureOptions,
+        samplers: &mut SamplerCache,
+        mipmaps: Option<&mut MipmapGenerator>
+    ) -> Result<Self, image::ImageError> {
+        let img = image::load_from_memory(bytes)?.to_rgba8();
+        let options = TextureOptions { normal_convention: None, ..*options };
+        let (texture, view) = Self::upload(device, queue, &img, wgpu::TextureFormat::Rgba8Unorm, label, &options, mipmaps);
+        Ok(Self {
+            size: uvec2(img.width(), img.height()),
+            texture,
+            view,
+            sampler: samplers.get(device, options.sampler),
+            normal_convention: None
+        })
+    }
+
+    /// A 1x1 texture, used in place of optional maps that a material doesn't provide.
+    pub fn solid_color(
+        device: &Device,
+        queue: &Queue,
+        color: [u8; 4],
+        format: wgpu::TextureFormat,
+        label: &str
+    ) -> Self {
+        let options = TextureOptions::default();
+        let (texture, view) = Self::upload(device, queue, &image::RgbaImage::from_pixel(1, 1, image::Rgba(color)), format, label, &options, None);
+        Self {
+            size: UVec2::ONE,
+            texture,
+            view,
+            sampler: Arc::new(options.sampler.create_sampler(device)),
+            normal_convention: None
+        }
+    }
+
+    fn upload(
+        device: &Device,
+        queue: &Queue,
+        img: &image::RgbaImage,
+        format: wgpu::TextureFormat,
+        label: &str,
+        options: &TextureOptions,
+        mipmaps: Option<&mut MipmapGenerator>
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let size = uvec2(img.width(), img.height());
+        let filter = match options.normal_convention {
+            Some(convention) => MipFilter::Normal { two_channel: matches!(convention, NormalMapConvention::TwoChannel { .. }) },
+            None => MipFilter::Color
+        };
+        let mip_level_count = match options.max_mip_levels {
+            _ if !options.mipmaps => 1,
+            Some(levels) => mip_level_count(size).min(levels).max(1),
+            None => mip_level_count(size)
         };
+        let generator = mipmaps.filter(|generator| mip_level_count > 1 && generator.renders(format));
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if generator.is_some() {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some(label),
-            size,
-            mip_level_count: 1,
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1
+            },
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: if is_normal_map {
-                wgpu::TextureFormat::Rgba8Unorm
-            } else {
-                wgpu::TextureFormat::Rgba8UnormSrgb
+            format,
+            usage
+        });
+
+        write_level(queue, &texture, 0, img, format);
+        match generator {
+            Some(generator) => generator.generate(device, queue, &texture, format, mip_level_count, filter),
+            None if mip_level_count > 1 => {
+                for (level, mip) in cpu_mip_chain(img, filter, format.describe().srgb).iter().take(mip_level_count as usize - 1).enumerate() {
+                    write_level(queue, &texture, level as u32 + 1, mip, format);
+                }
+            },
+            None => {}
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    pub fn create_render_target(
+        device: &Device,
+        size: UVec2,
+        format: wgpu::TextureFormat,
+        label: &str
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1
             },
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = Arc::new(SamplerSettings::pixel_art().create_sampler(device));
+
+        Self {
+            size,
+            texture,
+            view,
+            sampler,
+            normal_convention: None
+        }
+    }
+
+    /// Copies an 8-bit RGBA texture back to the CPU. Rows are padded to
+    /// `COPY_BYTES_PER_ROW_ALIGNMENT` on the GPU side and stripped here.
+    pub fn read_to_image(&self, device: &Device, queue: &Queue) -> image::RgbaImage {
+        let unpadded_bytes_per_row = 4 * self.size.x;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("texture_readback_buffer"),
+            size: (padded_bytes_per_row * self.size.y) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
         });
 
-        queue.write_texture(
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("texture_readback_encoder") });
+        encoder.copy_texture_to_buffer(
             wgpu::ImageCopyTexture {
                 aspect: wgpu::TextureAspect::All,
-                texture: &texture,
+                texture: &self.texture,
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: NonZeroU32::new(4 * dimensions.0),
-                rows_per_image: NonZeroU32::new(dimensions.1),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(self.size.y),
+                }
             },
-            size,
+            wgpu::Extent3d {
+                width: self.size.x,
+                height: self.size.y,
+                depth_or_array_layers: 1
+            }
         );
+        queue.submit(core::iter::once(encoder.finish()));
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping).unwrap();
 
-        Self {
-            size: uvec2(size.width, size.height),
-            texture,
-            view,
-            sampler
+        let padded = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * self.size.y) as usize);
+        for row in padded.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
         }
+        drop(padded);
+        buffer.unmap();
 
+        image::RgbaImage::from_raw(self.size.x, self.size.y, pixels).unwrap()
     }
-}
\ No newline at end of file
+}
+
+/// Uploads one mip level, dropping blue and alpha for two-channel formats.
+fn write_level(queue: &Queue, texture: &wgpu::Texture, level: u32, img: &image::RgbaImage, format: wgpu::TextureFormat) {
+    let (pixels, bytes_per_pixel) = match format {
+        wgpu::TextureFormat::Rg8Unorm => (img.pixels().flat_map(|p| [p[0], p[1]]).collect(), 2),
+        _ => (img.as_raw().clone(), 4)
+    };
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture,
+            mip_level: level,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        &pixels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(bytes_per_pixel * img.width()),
+            rows_per_image: NonZeroU32::new(img.height()),
+        },
+        wgpu::Extent3d {
+            width: img.width(),
+            height: img.height(),
+            depth_or_array_layers: 1
+        },
+    );
+}
@@ -0,0 +1,207 @@
+use std::borrow::Cow;
+
+use crevice::std140::{AsStd140, Std140};
+use serde::{Deserialize, Serialize};
+use wgpu::{BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferSize, BufferUsages, CommandEncoder, Device, Queue, RenderPipeline, Sampler, SamplerBindingType, ShaderStages, TextureFormat, TextureSampleType, TextureView};
+
+use super::SamplerSettings;
+
+/// Format the forward pass renders into before tonemapping.
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Curve that maps HDR color into the displayable 0-1 range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Tonemapper {
+    /// Clamps, so anything brighter than 1 clips to white. The default, which
+    /// keeps the look from before HDR rendering.
+    #[default]
+    None,
+    /// `c / (1 + c)` per channel. Never quite reaches white.
+    Reinhard,
+    /// Fit of the ACES filmic curve, with more contrast and a soft shoulder.
+    Aces
+}
+
+/// Scenes opt into a curve; by default color is clamped with no exposure change.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TonemapSettings {
+    pub tonemapper: Tonemapper,
+    /// In stops: each +1 doubles the scene color before the curve.
+    pub exposure: f32
+}
+
+impl TonemapSettings {
+    /// Passes color through unchanged apart from clamping.
+    pub const fn passthrough() -> Self {
+        Self { tonemapper: Tonemapper::None, exposure: 0.0 }
+    }
+}
+
+#[derive(AsStd140)]
+pub struct GPUTonemapSettings {
+    /// Linear scale, `2^exposure`.
+    pub exposure: f32,
+    pub tonemapper: u32,
+    /// Scale of the bloom texture added to the HDR color, 0 without bloom.
+    pub bloom_intensity: f32
+}
+
+impl GPUTonemapSettings {
+    pub fn new(settings: TonemapSettings, bloom_intensity: f32) -> Self {
+        Self {
+            exposure: settings.exposure.exp2(),
+            tonemapper: settings.tonemapper as u32,
+            bloom_intensity
+        }
+    }
+}
+
+/// Fullscreen pass that adds the bloom to the HDR target and tonemaps the
+/// result into the final view.
+pub struct TonemapPass {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    settings: Buffer,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline
+}
+
+impl TonemapPass {
+    pub fn new(device: &Device, source: &str, target_format: TextureFormat, hdr: &TextureView, bloom: &TextureView) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("tonemap_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: false }
+                    },
+                    count: None
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(GPUTonemapSettings::std140_size_static() as u64)
+                    },
+                    count: None
+                }
+            ]
+        });
+        let settings = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tonemap_settings_buffer"),
+            size: GPUTonemapSettings::std140_size_static() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("tonemap_shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source))
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("tonemap_pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tonemap_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: target_format,
+                    // Uncovered HDR texels are transparent when the view isn't cleared.
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None
+        });
+
+        let sampler = SamplerSettings::trilinear().create_sampler(device);
+        let bind_group = create_bind_group(device, &layout, &sampler, &settings, hdr, bloom);
+        Self { layout, sampler, settings, bind_group, pipeline }
+    }
+
+    /// Points the pass at new HDR and bloom textures, after a resize.
+    pub fn set_inputs(&mut self, device: &Device, hdr: &TextureView, bloom: &TextureView) {
+        self.bind_group = create_bind_group(device, &self.layout, &self.sampler, &self.settings, hdr, bloom);
+    }
+
+    pub fn prepare(&self, queue: &Queue, settings: TonemapSettings, bloom_intensity: f32) {
+        queue.write_buffer(&self.settings, 0, GPUTonemapSettings::new(settings, bloom_intensity).as_std140().as_bytes());
+    }
+
+    /// Draws the tonemapped HDR target over `view`, clearing it first with `load`.
+    pub fn render(&self, encoder: &mut CommandEncoder, view: &TextureView, load: wgpu::LoadOp<wgpu::Color>) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("tonemap_pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true }
+            }],
+            depth_stencil_attachment: None
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_bind_group(device: &Device, layout: &BindGroupLayout, sampler: &Sampler, settings: &Buffer, hdr: &TextureView, bloom: &TextureView) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("tonemap_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(hdr) },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(bloom) },
+            wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(sampler) },
+            wgpu::BindGroupEntry { binding: 3, resource: settings.as_entire_binding() }
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let settings = GPUTonemapSettings::new(TonemapSettings { tonemapper: Tonemapper::Reinhard, exposure: 2.0 }, 0.0);
+        assert_eq!(settings.exposure, 4.0);
+        assert_eq!(settings.tonemapper, 1);
+        assert_eq!(GPUTonemapSettings::new(TonemapSettings::passthrough(), 0.0).exposure, 1.0);
+    }
+}
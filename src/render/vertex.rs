@@ -1,8 +1,12 @@
 
 pub const U16_SIZE: wgpu::BufferAddress = std::mem::size_of::<u16>() as wgpu::BufferAddress;
+/// `tangent.w` is the handedness sign: the bitangent is `cross(normal, tangent.xyz) * tangent.w`
+/// and points towards the top of the texture.
 pub struct Vertex {
     pub position: [f32; 3],
-    pub uv: [f32;2]
+    pub uv: [f32;2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 4]
 }
 
 impl Vertex {
@@ -13,6 +17,8 @@ impl Vertex {
         attributes: &wgpu::vertex_attr_array![
             0 => Float32x3,
             1 => Float32x2,
+            2 => Float32x3,
+            3 => Float32x4,
         ]
     };
 }
\ No newline at end of file
@@ -0,0 +1,1018 @@
+use std::{borrow::Cow, fmt, sync::Arc, time::{Duration, Instant}};
+
+use crevice::std140::{AsStd140, Std140};
+use glam::{Mat4, UVec2};
+use wgpu::{Adapter, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendComponent, Buffer, BufferBindingType, BufferDescriptor, BufferSize, BufferUsages, CommandEncoder, ComputePipeline, Device, Instance, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, Surface, SurfaceConfiguration, TextureFormat, TextureView, VertexState};
+use winit::window::Window;
+
+use crate::{components::{pack_lights, EyePosition, GPUDirectionalLight, GPUPointLight, GPUSceneLights, GPUSpotLight}, render::{create_cull_layout, create_cull_pipeline, cull_shader_source, specialize, validate_wgsl, AssetError, AssetManager, BloomPass, BloomSettings, DebugView, GPUDebugSettings, GPULightGrid, Handle, LightCullingMode, LightGrid, Material, MaterialRegistry, MipmapGenerator, SamplerSettings, Shader, ShaderError, SpriteBatcher, SpriteInstance, Texture, TonemapPass, TonemapSettings, Vertex, HDR_FORMAT}, scene::Scene};
+
+/// How often `hot_reload` checks the asset files for changes.
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Requested size of the point light array, clamped to what the device's
+/// uniform binding size allows.
+const MAX_POINT_LIGHTS: u32 = 256;
+
+/// Directional and spot lights are few and not tile culled; every fragment loops over all of them.
+const MAX_DIRECTIONAL_LIGHTS: u32 = 4;
+const MAX_SPOT_LIGHTS: u32 = 32;
+
+/// Format of the offscreen target used by headless renderers.
+pub const HEADLESS_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Debug)]
+pub enum RendererError {
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The adapter can't present to the window's surface.
+    IncompatibleSurface,
+    /// The adapter can't render to `HDR_FORMAT` with blending or sample it with filtering.
+    UnsupportedHdrFormat,
+    Asset(AssetError),
+    Shader(ShaderError)
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::NoAdapter => write!(f, "no compatible adapter available"),
+            RendererError::RequestDevice(error) => write!(f, "failed to create device: {}", error),
+            RendererError::IncompatibleSurface => write!(f, "the adapter can't present to the window surface"),
+            RendererError::UnsupportedHdrFormat => write!(f, "the adapter can't render to or filter {:?} textures", HDR_FORMAT),
+            RendererError::Asset(error) => write!(f, "{}", error),
+            RendererError::Shader(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+impl From<AssetError> for RendererError {
+    fn from(error: AssetError) -> Self {
+        RendererError::Asset(error)
+    }
+}
+
+impl From<ShaderError> for RendererError {
+    fn from(error: ShaderError) -> Self {
+        RendererError::Shader(error)
+    }
+}
+
+enum RenderTarget {
+    Surface { surface: Surface, config: SurfaceConfiguration },
+    Offscreen(Texture),
+    /// Views are passed to `render` by the application that owns the device.
+    External
+}
+
+/// The shaders the pipelines are built from, and the constants their arrays
+/// are specialized with for the device.
+struct ForwardShaders {
+    vertex: Handle<Shader>,
+    fragment: Handle<Shader>,
+    /// Declares the light tile lookups for the active `LightCullingMode`.
+    light_tiles: Handle<Shader>,
+    light_cull: Option<Handle<Shader>>,
+    max_point_lights: u32,
+    constants: Vec<(&'static str, u32)>
+}
+
+impl ForwardShaders {
+    fn load(assets: &mut AssetManager, light_culling: LightCullingMode, max_point_lights: u32, light_grid: &LightGrid) -> Result<Self, AssetError> {
+        Ok(Self {
+            vertex: assets.load_shader("vertex.wgsl")?,
+            fragment: assets.load_shader("frag.wgsl")?,
+            light_tiles: assets.load_shader(light_culling.fragment_shader_path())?,
+            light_cull: match light_culling {
+                LightCullingMode::Compute => Some(assets.load_shader("light_cull.wgsl")?),
+                LightCullingMode::Cpu => None
+            },
+            max_point_lights,
+            constants: [
+                [
+                    ("MAX_POINT_LIGHTS", max_point_lights),
+                    ("MAX_DIRECTIONAL_LIGHTS", MAX_DIRECTIONAL_LIGHTS),
+                    ("MAX_SPOT_LIGHTS", MAX_SPOT_LIGHTS)
+                ].as_slice(),
+                &light_grid.shader_constants()
+            ].concat()
+        })
+    }
+
+    /// The light tile declarations for the culling mode followed by `frag.wgsl`.
+    fn fragment_source(&self, assets: &AssetManager) -> String {
+        specialize(&[assets.shader_source(&self.light_tiles), assets.shader_source(&self.fragment)].join("\n"), &self.constants)
+    }
+
+    /// Checks the forward and culling shaders with naga, naming the file and
+    /// line of the first error.
+    fn validate(&self, assets: &AssetManager) -> Result<(), ShaderError> {
+        let name = |handle: &Handle<Shader>| assets.shaders.path(handle).map_or_else(|| "shader".to_string(), |path| path.display().to_string());
+        let vertex = assets.shader_source(&self.vertex);
+        validate_wgsl(&[(&name(&self.vertex), vertex)], vertex)?;
+        validate_wgsl(&[
+            (&name(&self.light_tiles), assets.shader_source(&self.light_tiles)),
+            (&name(&self.fragment), assets.shader_source(&self.fragment))
+        ], &self.fragment_source(assets))?;
+        if let Some(light_cull) = self.light_cull.as_ref() {
+            let cull = assets.shader_source(light_cull);
+            validate_wgsl(&[(&name(light_cull), cull)], &cull_shader_source(cull, self.max_point_lights))?;
+        }
+        Ok(())
+    }
+}
+
+struct ForwardBuffers {
+    camera: Buffer,
+    eye: Buffer,
+    debug: Buffer,
+    lights: Buffer,
+    scene_lights: Buffer,
+    directional_lights: Buffer,
+    spot_lights: Buffer,
+    light_grid: Buffer,
+    tile_counts: Buffer,
+    tile_indices: Buffer
+}
+
+impl ForwardBuffers {
+    fn new(device: &Device, light_culling: LightCullingMode, max_point_lights: u32, light_grid: &LightGrid) -> Self {
+        let buffer = |label, size: u64, usage| device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size,
+            mapped_at_creation: false,
+            usage
+        });
+        let uniform = BufferUsages::UNIFORM | BufferUsages::COPY_DST;
+
+        Self {
+            camera: buffer("camera_buffer", Mat4::std140_size_static() as u64, uniform),
+            eye: buffer("eye_position_buffer", EyePosition::std140_size_static() as u64, uniform),
+            debug: buffer("debug_settings_buffer", GPUDebugSettings::std140_size_static() as u64, uniform),
+            lights: buffer("gpu_light_buffer", max_point_lights as u64 * GPUPointLight::std140_size_static() as u64, uniform),
+            scene_lights: buffer("scene_lights_buffer", GPUSceneLights::std140_size_static() as u64, uniform),
+            directional_lights: buffer("directional_light_buffer", MAX_DIRECTIONAL_LIGHTS as u64 * GPUDirectionalLight::std140_size_static() as u64, uniform),
+            spot_lights: buffer("spot_light_buffer", MAX_SPOT_LIGHTS as u64 * GPUSpotLight::std140_size_static() as u64, uniform),
+            light_grid: buffer("light_grid_buffer", GPULightGrid::std140_size_static() as u64, uniform),
+            tile_counts: buffer("tile_light_counts_buffer", light_grid.counts_size(), light_culling.tile_buffer_usage()),
+            tile_indices: buffer("tile_light_indices_buffer", light_grid.indices_size(), light_culling.tile_buffer_usage())
+        }
+    }
+}
+
+/// The compute pass that fills the light tile lists on capable adapters.
+struct LightCullPass {
+    layout: BindGroupLayout,
+    bind_group: BindGroup,
+    pipeline: ComputePipeline
+}
+
+/// Configures and creates a `Renderer`.
+pub struct RendererBuilder {
+    size: UVec2,
+    assets: AssetManager,
+    hot_reload: bool,
+    filtering: bool
+}
+
+impl RendererBuilder {
+    pub fn new(size: UVec2) -> Self {
+        Self { size, assets: AssetManager::default(), hot_reload: false, filtering: false }
+    }
+
+    /// Where textures, shaders and scenes are loaded from, `res/` by default.
+    pub fn with_assets(mut self, assets: AssetManager) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    /// Reload edited shaders and textures in `Renderer::hot_reload`.
+    pub fn with_hot_reload(mut self, hot_reload: bool) -> Self {
+        self.hot_reload = hot_reload;
+        self
+    }
+
+    /// Sample textures with linear filtering and build mip chains for every
+    /// texture loaded afterwards, instead of the default nearest sampling.
+    pub fn with_filtering(mut self, filtering: bool) -> Self {
+        self.filtering = filtering;
+        self
+    }
+
+    /// Renders to the window's swapchain.
+    pub async fn build_for_window(self, window: &Window) -> Result<Renderer, RendererError> {
+        let instance = Instance::new(wgpu::Backends::all());
+        let surface = unsafe { instance.create_surface(window) };
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: Some(&surface)
+        }).await
+        .ok_or(RendererError::NoAdapter)?;
+
+        let (light_culling, device, queue) = request_device(&adapter).await?;
+        let config = SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface.get_preferred_format(&adapter).ok_or(RendererError::IncompatibleSurface)?,
+            width: self.size.x,
+            height: self.size.y,
+            present_mode: wgpu::PresentMode::Immediate
+        };
+        surface.configure(&device, &config);
+        let format = config.format;
+        self.finish(Some(instance), device, queue, light_culling, RenderTarget::Surface { surface, config }, format)
+    }
+
+    /// Renders into an offscreen texture without a window or surface, falling
+    /// back to a software adapter if there is no hardware one.
+    pub async fn build_headless(self) -> Result<Renderer, RendererError> {
+        let instance = Instance::new(wgpu::Backends::all());
+        let mut adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter: false,
+            compatible_surface: None
+        }).await;
+        if adapter.is_none() {
+            adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter: true,
+                compatible_surface: None
+            }).await;
+        }
+        let adapter = adapter.ok_or(RendererError::NoAdapter)?;
+
+        let (light_culling, device, queue) = request_device(&adapter).await?;
+        let render_target = Texture::create_render_target(&device, self.size, HEADLESS_FORMAT, "headless_target");
+        self.finish(Some(instance), device, queue, light_culling, RenderTarget::Offscreen(render_target), HEADLESS_FORMAT)
+    }
+
+    /// Shares a device the application already created, for drawing into its
+    /// own views with `Renderer::render`. `target_format` is the format of
+    /// those views.
+    pub fn build_with_device(self, adapter: &Adapter, device: Arc<Device>, queue: Arc<Queue>, target_format: TextureFormat) -> Result<Renderer, RendererError> {
+        check_hdr_support(adapter)?;
+        let light_culling = LightCullingMode::for_device(adapter, &device);
+        self.finish(None, device, queue, light_culling, RenderTarget::External, target_format)
+    }
+
+    fn finish(self, instance: Option<Instance>, device: Arc<Device>, queue: Arc<Queue>, light_culling: LightCullingMode, target: RenderTarget, target_format: TextureFormat) -> Result<Renderer, RendererError> {
+        let mut assets = self.assets;
+        let max_point_lights = GPUPointLight::max_lights(&device.limits(), MAX_POINT_LIGHTS);
+        let light_grid = LightGrid::new(self.size, light_culling.max_lights_per_tile(&device.limits(), max_point_lights));
+        let shaders = ForwardShaders::load(&mut assets, light_culling, max_point_lights, &light_grid)?;
+        shaders.validate(&assets)?;
+        if self.filtering {
+            let mipmap_shader = assets.load_shader("mipmap.wgsl")?;
+            let source = assets.shader_source(&mipmap_shader);
+            validate_wgsl(&[("mipmap.wgsl", source)], source)?;
+            let mipmaps = MipmapGenerator::new(&device, source);
+            assets.mipmaps = Some(mipmaps);
+            assets.filtering = true;
+            assets.sampler = SamplerSettings::trilinear();
+        }
+
+        let tonemap_shader = assets.load_shader("tonemap.wgsl")?;
+        let bloom_shader = assets.load_shader("bloom.wgsl")?;
+        let tonemap_source = assets.shader_source(&tonemap_shader);
+        validate_wgsl(&[("tonemap.wgsl", tonemap_source)], tonemap_source)?;
+        let bloom_source = assets.shader_source(&bloom_shader);
+        validate_wgsl(&[("bloom.wgsl", bloom_source)], bloom_source)?;
+        let hdr = Texture::create_render_target(&device, self.size, HDR_FORMAT, "hdr_target");
+        let bloom = BloomPass::new(&device, bloom_source, self.size);
+        let tonemap = TonemapPass::new(&device, tonemap_source, target_format, &hdr.view, bloom.output());
+
+        let layouts = create_layouts(&device, light_culling, max_point_lights, &light_grid, self.filtering);
+        let buffers = ForwardBuffers::new(&device, light_culling, max_point_lights, &light_grid);
+        let bind_groups = create_bind_groups(&device, &layouts, &buffers);
+        let cull_layout = (light_culling == LightCullingMode::Compute).then(|| create_cull_layout(&device, max_point_lights));
+        let (pipeline, cull_pipeline) = create_pipelines(&device, &layouts, cull_layout.as_ref(), &assets, &shaders);
+        let light_cull = cull_layout.zip(cull_pipeline).map(|(layout, pipeline)| LightCullPass {
+            bind_group: create_cull_bind_group(&device, &layout, &buffers),
+            layout,
+            pipeline
+        });
+
+        let mut sprite_batcher = SpriteBatcher::default();
+        sprite_batcher.build_buffers(&device);
+
+        Ok(Renderer {
+            instance,
+            device,
+            queue,
+            target,
+            target_format,
+            light_culling,
+            max_point_lights,
+            filtering: self.filtering,
+            assets,
+            materials: MaterialRegistry::default(),
+            debug_view: DebugView::default(),
+            clear_color: Some(wgpu::Color::BLACK),
+            hot_reload: self.hot_reload,
+            last_reload_poll: None,
+            shaders,
+            light_grid,
+            emissive_lights: Vec::new(),
+            minimized: false,
+            dropped_tile_lights: 0,
+            scissor: None,
+            layouts,
+            buffers,
+            bind_groups,
+            light_cull,
+            pipeline,
+            hdr,
+            bloom,
+            tonemap,
+            sprite_batcher
+        })
+    }
+}
+
+async fn request_device(adapter: &Adapter) -> Result<(LightCullingMode, Arc<Device>, Arc<Queue>), RendererError> {
+    check_hdr_support(adapter)?;
+    let light_culling = LightCullingMode::for_adapter(adapter);
+    let (device, queue) = adapter
+    .request_device(&wgpu::DeviceDescriptor {
+        label: None,
+        features: wgpu::Features::empty(),
+        limits: light_culling.limits(adapter)
+    }, None)
+    .await
+    .map_err(RendererError::RequestDevice)?;
+    Ok((light_culling, Arc::new(device), Arc::new(queue)))
+}
+
+/// The HDR target and bloom chain are blended into and sampled with
+/// filtering, which WebGL2 only allows with `EXT_color_buffer_float` and
+/// `EXT_float_blend`.
+fn check_hdr_support(adapter: &Adapter) -> Result<(), RendererError> {
+    let features = adapter.get_texture_format_features(HDR_FORMAT);
+    if features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT) && features.filterable {
+        Ok(())
+    } else {
+        Err(RendererError::UnsupportedHdrFormat)
+    }
+}
+
+/// Owns the GPU device and everything needed to draw a `Scene`: the target,
+/// assets, materials, pipelines and per-frame buffers.
+pub struct Renderer {
+    /// `None` when built with an application's device.
+    pub instance: Option<Instance>,
+    pub device: Arc<Device>,
+    pub queue: Arc<Queue>,
+    target: RenderTarget,
+    pub target_format: TextureFormat,
+    pub light_culling: LightCullingMode,
+    pub max_point_lights: u32,
+    /// Whether the material layout expects filtering samplers and textures
+    /// get mip chains. Fixed when the renderer is built.
+    pub filtering: bool,
+    pub assets: AssetManager,
+    pub materials: MaterialRegistry,
+    pub debug_view: DebugView,
+    /// Color `render` clears the view to. `None` draws over what the view
+    /// already holds.
+    pub clear_color: Option<wgpu::Color>,
+    /// Reload edited shaders and textures in `hot_reload`.
+    pub hot_reload: bool,
+    last_reload_poll: Option<Instant>,
+    shaders: ForwardShaders,
+    light_grid: LightGrid,
+    /// Lights cast by sprites with an `EmissiveLight` material, packed after
+    /// the scene's point lights.
+    emissive_lights: Vec<GPUPointLight>,
+    /// The last resize was to a zero sized (minimized) window.
+    minimized: bool,
+    /// See `dropped_tile_lights`.
+    dropped_tile_lights: u32,
+    /// Letterbox rectangle from the camera's resize policy.
+    scissor: Option<[u32; 4]>,
+    /// Pass, material, lights and light tiles.
+    layouts: [BindGroupLayout; 4],
+    buffers: ForwardBuffers,
+    /// Pass, lights and light tiles; materials own their bind groups.
+    bind_groups: [BindGroup; 3],
+    light_cull: Option<LightCullPass>,
+    pipeline: RenderPipeline,
+    /// What the forward pass draws into, before bloom and tonemapping.
+    hdr: Texture,
+    bloom: BloomPass,
+    tonemap: TonemapPass,
+    sprite_batcher: SpriteBatcher
+}
+
+impl Renderer {
+    pub fn builder(size: UVec2) -> RendererBuilder {
+        RendererBuilder::new(size)
+    }
+
+    pub fn material_layout(&self) -> &BindGroupLayout {
+        &self.layouts[1]
+    }
+
+    /// The offscreen texture of a headless renderer.
+    pub fn offscreen_target(&self) -> Option<&Texture> {
+        match &self.target {
+            RenderTarget::Offscreen(texture) => Some(texture),
+            RenderTarget::Surface { .. } | RenderTarget::External => None
+        }
+    }
+
+    pub fn target_size(&self) -> UVec2 {
+        self.light_grid.target_size
+    }
+
+    /// Reconfigures the surface, or recreates the offscreen target, at the new
+    /// size. With an application's device only the light tiles change. The
+    /// scene must be prepared again before the next frame. A zero size, as
+    /// sent when the window is minimized, keeps the old target and skips
+    /// frames until the next resize.
+    pub fn resize(&mut self, size: UVec2) {
+        self.minimized = size.x == 0 || size.y == 0;
+        if self.minimized {
+            return;
+        }
+        match &mut self.target {
+            RenderTarget::Surface { surface, config } => {
+                config.width = size.x;
+                config.height = size.y;
+                surface.configure(&self.device, config);
+            },
+            RenderTarget::Offscreen(texture) => {
+                *texture = Texture::create_render_target(&self.device, size, self.target_format, "headless_target");
+            },
+            RenderTarget::External => {}
+        }
+        self.hdr = Texture::create_render_target(&self.device, size, HDR_FORMAT, "hdr_target");
+        self.bloom.resize(&self.device, size, &self.hdr.view);
+        self.tonemap.set_inputs(&self.device, &self.hdr.view, self.bloom.output());
+        self.light_grid.target_size = size;
+    }
+
+    /// Uploads the scene's camera, lights and sprites, the debug view, and any
+    /// materials that changed.
+    pub fn prepare(&mut self, scene: &mut Scene) {
+        self.upload_camera(scene);
+        self.emissive_lights = self.materials.emissive_lights(&scene.sprites);
+        let queue = &self.queue;
+        let buffers = &self.buffers;
+
+        for light in scene.lights.iter_mut() {
+            light.sync_position();
+        }
+        for light in scene.directional_lights.iter_mut() {
+            light.sync_direction();
+        }
+        for light in scene.spot_lights.iter_mut() {
+            light.sync();
+        }
+        let directional_bytes = pack_lights(scene.directional_lights.iter().map(|light| &light.gpu_light), MAX_DIRECTIONAL_LIGHTS);
+        if !directional_bytes.is_empty() {
+            queue.write_buffer(&buffers.directional_lights, 0, &directional_bytes);
+        }
+        let spot_bytes = pack_lights(scene.spot_lights.iter().map(|light| &light.gpu_light), MAX_SPOT_LIGHTS);
+        if !spot_bytes.is_empty() {
+            queue.write_buffer(&buffers.spot_lights, 0, &spot_bytes);
+        }
+        self.upload_point_lights(scene);
+
+        queue.write_buffer(&buffers.light_grid, 0, self.light_grid.to_gpu().as_std140().as_bytes());
+        queue.write_buffer(&buffers.debug, 0, GPUDebugSettings::from(self.debug_view).as_std140().as_bytes());
+        // Debug views show raw values, so they skip bloom, the curve and exposure.
+        let (tonemap, bloom) = if self.debug_view == DebugView::Lit {
+            (scene.tonemap, scene.bloom)
+        } else {
+            (TonemapSettings::passthrough(), BloomSettings { enabled: false, ..scene.bloom })
+        };
+        if self.bloom.prepare(&self.device, queue, &bloom, &self.hdr.view) {
+            self.tonemap.set_inputs(&self.device, &self.hdr.view, self.bloom.output());
+        }
+        let bloom_intensity = if self.bloom.is_active() { bloom.intensity } else { 0.0 };
+        self.tonemap.prepare(queue, tonemap, bloom_intensity);
+        self.materials.prepare(&self.device, queue, &self.layouts[1], &self.assets.textures);
+        self.sprite_batcher.prepare(&self.device, queue, &scene.sprites);
+        self.cull_lights(scene);
+    }
+
+    /// Re-uploads only the camera, after it was moved or zoomed, along with
+    /// the light tiles when they are culled on the CPU. Does nothing unless
+    /// the camera is dirty.
+    pub fn prepare_camera(&mut self, scene: &mut Scene) {
+        if self.upload_camera(scene) {
+            self.cull_lights(scene);
+        }
+    }
+
+    /// Refits the camera to the target, then uploads it if it changed.
+    fn upload_camera(&mut self, scene: &mut Scene) -> bool {
+        let target_size = self.light_grid.target_size;
+        if scene.camera.viewport_size != target_size {
+            scene.camera.resize(target_size);
+        }
+        self.scissor = scene.camera.resize_policy.scissor(target_size);
+        if !scene.camera.update() {
+            return false;
+        }
+        self.queue.write_buffer(&self.buffers.camera, 0, bytemuck::cast_slice(&[scene.camera.view_proj_matrix]));
+        self.queue.write_buffer(&self.buffers.eye, 0, scene.camera.eye_position().as_std140().as_bytes());
+        true
+    }
+
+    /// Scene point lights followed by the emissive sprite lights, up to `max_point_lights`.
+    fn point_lights<'a>(&'a self, scene: &'a Scene) -> impl Iterator<Item = &'a GPUPointLight> {
+        scene.lights.iter()
+            .map(|light| &light.gpu_light)
+            .chain(self.emissive_lights.iter())
+            .take(self.max_point_lights as usize)
+    }
+
+    /// Writes the point lights and the light counts and ambient.
+    fn upload_point_lights(&self, scene: &Scene) {
+        let light_bytes = pack_lights(self.point_lights(scene), self.max_point_lights);
+        if !light_bytes.is_empty() {
+            self.queue.write_buffer(&self.buffers.lights, 0, &light_bytes);
+        }
+        let scene_lights = GPUSceneLights {
+            count: self.point_lights(scene).count() as u32,
+            directional_count: scene.directional_lights.len().min(MAX_DIRECTIONAL_LIGHTS as usize) as u32,
+            spot_count: scene.spot_lights.len().min(MAX_SPOT_LIGHTS as usize) as u32,
+            ambient: scene.ambient.into()
+        };
+        self.queue.write_buffer(&self.buffers.scene_lights, 0, scene_lights.as_std140().as_bytes());
+    }
+
+    fn cull_lights(&mut self, scene: &Scene) {
+        if self.light_culling == LightCullingMode::Cpu {
+            let lights = self.point_lights(scene);
+            let tiles = self.light_grid.cull(lights, scene.camera.view_proj_matrix);
+            self.queue.write_buffer(&self.buffers.tile_counts, 0, bytemuck::cast_slice(&tiles.counts));
+            self.queue.write_buffer(&self.buffers.tile_indices, 0, bytemuck::cast_slice(&tiles.indices));
+            if tiles.dropped > 0 && self.dropped_tile_lights == 0 {
+                eprintln!("{} lights left out of full light tiles ({} per tile)", tiles.dropped, self.light_grid.max_lights_per_tile);
+            }
+            self.dropped_tile_lights = tiles.dropped;
+        }
+    }
+
+    /// Light and tile pairs the last CPU light culling left out because the
+    /// tile's list was full. Always 0 with compute culling, whose lists hold
+    /// every point light.
+    pub fn dropped_tile_lights(&self) -> u32 {
+        self.dropped_tile_lights
+    }
+
+    /// Re-uploads only the sprite instances, after sprites moved or changed
+    /// frame, along with the lights of emissive sprites if there are any.
+    pub fn prepare_sprites(&mut self, scene: &Scene) {
+        self.sprite_batcher.prepare(&self.device, &self.queue, &scene.sprites);
+        if self.materials.has_emissive_lights() {
+            self.emissive_lights = self.materials.emissive_lights(&scene.sprites);
+            self.upload_point_lights(scene);
+            self.cull_lights(scene);
+        }
+    }
+
+    /// Records the prepared scene into `encoder`, clearing `view` to
+    /// `clear_color` first, so it can be composited with the application's own passes. The view must
+    /// have `target_format` and the size the renderer was built or resized to.
+    pub fn render(&self, view: &TextureView, encoder: &mut CommandEncoder) {
+        if let Some(light_cull) = self.light_cull.as_ref() {
+            let (x, y) = self.light_grid.workgroups();
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("light_cull_pass") });
+            pass.set_pipeline(&light_cull.pipeline);
+            pass.set_bind_group(0, &light_cull.bind_group, &[]);
+            pass.dispatch(x, y, 1);
+        }
+
+        let render_pass_descriptor = RenderPassDescriptor {
+            label: Some("render_pass_descriptor"),
+            color_attachments: &[
+                RenderPassColorAttachment {
+                    view: &self.hdr.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color.unwrap_or(wgpu::Color::TRANSPARENT)),
+                        store: true,
+                    },
+                },
+            ],
+            depth_stencil_attachment: None
+        };
+        let mut pass = encoder.begin_render_pass(&render_pass_descriptor);
+        pass.set_pipeline(&self.pipeline);
+        if let Some([x, y, width, height]) = self.scissor {
+            pass.set_scissor_rect(x, y, width, height);
+        }
+        pass.set_bind_group(0, &self.bind_groups[0], &[]);  // view/camera
+        pass.set_bind_group(2, &self.bind_groups[1], &[]);  // lights
+        pass.set_bind_group(3, &self.bind_groups[2], &[]);  // light tiles
+        self.sprite_batcher.draw(&mut pass, 1, |material| self.materials.bind_group(material));  // material/textures
+        drop(pass);
+
+        self.bloom.render(encoder);
+        self.tonemap.render(encoder, view, self.clear_color.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear));
+    }
+
+    /// Renders the prepared scene to the window and presents it. Does nothing
+    /// unless the renderer was built for a window, or while it is minimized.
+    /// A lost or outdated surface is reconfigured and the frame skipped.
+    pub fn render_frame(&self) -> Result<(), wgpu::SurfaceError> {
+        let (surface, config) = match &self.target {
+            RenderTarget::Surface { surface, config } if !self.minimized => (surface, config),
+            _ => return Ok(())
+        };
+        let output = match surface.get_current_texture() {
+            Ok(output) => output,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                surface.configure(&self.device, config);
+                return Ok(());
+            },
+            Err(error) => return Err(error)
+        };
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.render(&view, &mut encoder);
+        self.queue.submit(core::iter::once(encoder.finish()));
+        output.present();
+        Ok(())
+    }
+
+    /// Applies edits to the files the assets were loaded from, at most every
+    /// `HOT_RELOAD_INTERVAL`. Shaders are validated before the pipeline is
+    /// rebuilt, and on any error the message is printed and the last good
+    /// pipeline or texture stays in use.
+    pub fn hot_reload(&mut self, scene: &mut Scene) {
+        let now = Instant::now();
+        if !self.hot_reload || self.last_reload_poll.is_some_and(|last| now - last < HOT_RELOAD_INTERVAL) {
+            return;
+        }
+        self.last_reload_poll = Some(now);
+
+        let mut shaders_changed = false;
+        let mut textures_changed = false;
+        let mut atlas_changed = false;
+        for path in self.assets.poll_changes() {
+            if self.assets.shaders.contains_path(&path) {
+                println!("reloading {}", path.display());
+                match self.assets.reload_shader(&path) {
+                    Ok(_) => shaders_changed = true,
+                    Err(error) => eprintln!("{}", error)
+                }
+            } else if self.assets.textures.contains_path(&path) {
+                println!("reloading {}", path.display());
+                match self.assets.reload_texture(&self.device, &self.queue, &path) {
+                    Ok(Some(handle)) => {
+                        self.materials.texture_changed(&handle);
+                        textures_changed = true;
+                    },
+                    Ok(None) => {},
+                    Err(error) => eprintln!("{}", error)
+                }
+            } else if scene.description.uses_atlas_source(&self.assets, &path) {
+                println!("reloading {}", path.display());
+                atlas_changed = true;
+            }
+        }
+
+        if atlas_changed {
+            match scene.reload_atlas(self) {
+                Ok(()) => textures_changed = true,
+                Err(error) => eprintln!("{}", error)
+            }
+        }
+        if shaders_changed {
+            self.rebuild_pipelines();
+        }
+        if shaders_changed || textures_changed {
+            self.prepare(scene);
+        }
+    }
+
+    fn rebuild_pipelines(&mut self) {
+        if let Err(error) = self.shaders.validate(&self.assets) {
+            eprintln!("{}", error);
+            return;
+        }
+
+        // Anything naga accepts but wgpu rejects, such as a binding that no
+        // longer matches the layout, is caught here instead of panicking.
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let cull_layout = self.light_cull.as_ref().map(|light_cull| &light_cull.layout);
+        let (pipeline, cull_pipeline) = create_pipelines(&self.device, &self.layouts, cull_layout, &self.assets, &self.shaders);
+        if let Some(error) = futures::executor::block_on(self.device.pop_error_scope()) {
+            eprintln!("{}", error);
+            return;
+        }
+        self.pipeline = pipeline;
+        if let (Some(light_cull), Some(pipeline)) = (self.light_cull.as_mut(), cull_pipeline) {
+            light_cull.pipeline = pipeline;
+        }
+    }
+
+}
+
+fn create_pipelines(device: &Device, layouts: &[BindGroupLayout; 4], cull_layout: Option<&BindGroupLayout>, assets: &AssetManager, shaders: &ForwardShaders) -> (RenderPipeline, Option<ComputePipeline>) {
+    let bind_group_layouts = &[&layouts[0], &layouts[1], &layouts[2], &layouts[3]];
+
+    let vert_shader = device.create_shader_module(&ShaderModuleDescriptor {
+        label: Some("vert_shader"),
+        source: ShaderSource::Wgsl(Cow::Borrowed(assets.shader_source(&shaders.vertex)))
+    });
+
+    let frag_shader = device.create_shader_module(&ShaderModuleDescriptor {
+        label: Some("frag_shader"),
+        source: ShaderSource::Wgsl(Cow::Owned(shaders.fragment_source(assets)))
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Render Pipeline Layout"),
+        bind_group_layouts,
+        push_constant_ranges: &[]
+    });
+
+    let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("point_light_pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &vert_shader,
+            entry_point: "vs",
+            buffers: &[Vertex::DESC, SpriteInstance::DESC]
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &frag_shader,
+            entry_point: "fs",
+            targets: &[wgpu::ColorTargetState {
+                format: HDR_FORMAT,
+                blend: Some(wgpu::BlendState {
+                    alpha: BlendComponent::REPLACE,
+                    color: BlendComponent::REPLACE,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: None,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: true,
+        },
+        multiview: None,
+        depth_stencil: None,
+    });
+
+    let cull_pipeline = cull_layout.zip(shaders.light_cull.as_ref())
+        .map(|(layout, shader)| create_cull_pipeline(device, layout, assets.shader_source(shader), shaders.max_point_lights));
+    (pipeline, cull_pipeline)
+}
+
+fn create_bind_groups(device: &Device, layouts: &[BindGroupLayout; 4], buffers: &ForwardBuffers) -> [BindGroup; 3] {
+    let pass_bind_group = device.create_bind_group(
+        &BindGroupDescriptor {
+            layout: &layouts[0],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.camera.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.debug.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.eye.as_entire_binding()
+                },
+            ],
+            label: Some("pass_bind_group")
+        }
+    );
+
+    let object_bind_group = device.create_bind_group(
+        &BindGroupDescriptor {
+            layout: &layouts[2],
+            entries: &[
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.lights.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.scene_lights.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: buffers.directional_lights.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: buffers.spot_lights.as_entire_binding()
+                }
+            ],
+            label: Some("lights_bind_group")
+        }
+    );
+
+    let light_tiles_bind_group = device.create_bind_group(
+        &BindGroupDescriptor {
+            layout: &layouts[3],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.light_grid.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.tile_counts.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.tile_indices.as_entire_binding()
+                }
+            ],
+            label: Some("light_tiles_bind_group")
+        }
+    );
+
+    [pass_bind_group, object_bind_group, light_tiles_bind_group]
+}
+
+fn create_cull_bind_group(device: &Device, layout: &BindGroupLayout, buffers: &ForwardBuffers) -> BindGroup {
+    device.create_bind_group(
+        &BindGroupDescriptor {
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.camera.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.lights.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.scene_lights.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: buffers.light_grid.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: buffers.tile_counts.as_entire_binding()
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: buffers.tile_indices.as_entire_binding()
+                }
+            ],
+            label: Some("light_cull_bind_group")
+        }
+    )
+}
+
+fn create_layouts(device: &Device, light_culling: LightCullingMode, max_point_lights: u32, light_grid: &LightGrid, filtering: bool) -> [BindGroupLayout; 4] {
+    let group_0 = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("pass_layout"),
+        entries: &[
+            BindGroupLayoutEntry {  // camera view projection
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(Mat4::std140_size_static() as u64)
+                },
+                count: None
+            },
+            BindGroupLayoutEntry {  // debug view selection
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(GPUDebugSettings::std140_size_static() as u64)
+                },
+                count: None
+            },
+            BindGroupLayoutEntry {  // eye position
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(EyePosition::std140_size_static() as u64)
+                },
+                count: None
+            }
+        ]
+    });
+
+    let group_1 = Material::create_bind_group_layout(device, filtering);
+
+    let group_2 = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("lights_layout"),
+        entries: &[
+            BindGroupLayoutEntry {  // point light array
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(max_point_lights as u64 * GPUPointLight::std140_size_static() as u64)
+                },
+                count: None
+            },
+            BindGroupLayoutEntry {  // light counts
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(GPUSceneLights::std140_size_static() as u64)
+                },
+                count: None
+            },
+            BindGroupLayoutEntry {  // directional light array
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(MAX_DIRECTIONAL_LIGHTS as u64 * GPUDirectionalLight::std140_size_static() as u64)
+                },
+                count: None
+            },
+            BindGroupLayoutEntry {  // spot light array
+                binding: 4,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(MAX_SPOT_LIGHTS as u64 * GPUSpotLight::std140_size_static() as u64)
+                },
+                count: None
+            }
+        ]
+    });
+
+    let tile_binding_size = |size: u64| BindingType::Buffer {
+        ty: light_culling.tile_binding_type(),
+        has_dynamic_offset: false,
+        min_binding_size: BufferSize::new(size)
+    };
+    let group_3 = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("light_tiles_layout"),
+        entries: &[
+            BindGroupLayoutEntry {  // tile grid
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(GPULightGrid::std140_size_static() as u64)
+                },
+                count: None
+            },
+            BindGroupLayoutEntry {  // tile light counts
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: tile_binding_size(light_grid.counts_size()),
+                count: None
+            },
+            BindGroupLayoutEntry {  // tile light indices
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: tile_binding_size(light_grid.indices_size()),
+                count: None
+            }
+        ]
+    });
+
+    [group_0, group_1, group_2, group_3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn renderer_can_move_between_threads() {
+        assert_send::<Texture>();
+        assert_send::<Renderer>();
+    }
+}
@@ -0,0 +1,639 @@
+use std::{fmt, path::{Path, PathBuf}};
+
+use glam::{vec3, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{components::{AmbientLight, AnimationClip, AnimationEvent, AsepriteSheet, DirectionalLight, GPUAttenuation, GPUBaseLight, GPUDirectionalLight, GPUPointLight, GPUSpecularAttributes, GPUSpotLight, PointLight, SpotLight, Sprite, SpriteSheetError, Transform}, render::{AssetError, AssetManager, AtlasBuilder, AtlasError, BloomSettings, Camera, Handle, Material, MaterialHandle, NormalMapConvention, ResizePolicy, Texture, TextureAtlas, TextureOptions, TonemapSettings}, renderer::Renderer};
+
+/// Pixels of edge extrusion around each atlas region. With filtering the
+/// atlas gets `padded_mip_levels(ATLAS_PADDING)` mip levels, three for 8.
+const ATLAS_PADDING: u32 = 8;
+
+/// Scene file loaded when `--scene` isn't given, relative to the asset directory.
+pub const DEFAULT_SCENE: &str = "scene.ron";
+
+/// Images packed into the sprite atlas under `name`. Paths are relative to
+/// the asset directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextureDescription {
+    pub name: String,
+    pub albedo: String,
+    #[serde(default)]
+    pub normal: Option<String>,
+    /// How `normal` encodes its normals; converted to the atlas's convention when packed.
+    #[serde(default = "default_normal_convention")]
+    pub normal_convention: NormalMapConvention
+}
+
+fn default_normal_convention() -> NormalMapConvention {
+    NormalMapConvention::YDown
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    /// How the bounds are fitted to the window or offscreen target.
+    pub resize: ResizePolicy,
+    /// Multiplies the bounds, so larger values show more of the scene.
+    pub zoom: f32,
+    pub position: Vec3
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self { resize: ResizePolicy::default(), zoom: 1.0, position: vec3(0.0, 0.0, 0.1) }
+    }
+}
+
+impl CameraDescription {
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            resize: camera.resize_policy,
+            zoom: camera.zoom,
+            position: camera.transform.translation
+        }
+    }
+
+    pub fn to_camera(&self) -> Camera {
+        let mut camera = Camera::default();
+        camera.resize_policy = self.resize;
+        camera.zoom = self.zoom;
+        camera.transform.translation = self.position;
+        camera
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDescription {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        Self::from(&Transform::identity())
+    }
+}
+
+impl From<&Transform> for TransformDescription {
+    fn from(transform: &Transform) -> Self {
+        Self { translation: transform.translation, rotation: transform.rotation, scale: transform.scale }
+    }
+}
+
+impl TransformDescription {
+    pub fn to_transform(&self) -> Transform {
+        Transform { translation: self.translation, rotation: self.rotation, scale: self.scale }
+    }
+}
+
+/// A sprite showing an atlas region with the atlas material.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpriteDescription {
+    pub region: String,
+    #[serde(default)]
+    pub transform: TransformDescription
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PointLightDescription {
+    pub position: Vec3,
+    pub base: GPUBaseLight,
+    pub atten: GPUAttenuation
+}
+
+impl PointLightDescription {
+    pub fn from_light(light: &PointLight) -> Self {
+        Self { position: light.transform.translation, base: light.gpu_light.base_light, atten: light.gpu_light.atten }
+    }
+
+    pub fn to_light(&self) -> PointLight {
+        PointLight {
+            transform: Transform::from_translation(self.position),
+            gpu_light: GPUPointLight { base_light: self.base, atten: self.atten, ..Default::default() },
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectionalLightDescription {
+    pub base: GPUBaseLight,
+    pub direction: Vec3
+}
+
+impl Default for DirectionalLightDescription {
+    fn default() -> Self {
+        Self { base: GPUBaseLight::default(), direction: -Vec3::Z }
+    }
+}
+
+impl DirectionalLightDescription {
+    pub fn from_light(light: &DirectionalLight) -> Self {
+        Self { base: light.gpu_light.base_light, direction: light.transform.rotation * -Vec3::Z }
+    }
+
+    pub fn to_light(&self) -> DirectionalLight {
+        DirectionalLight {
+            transform: Transform { rotation: rotation_towards(self.direction), ..Default::default() },
+            gpu_light: GPUDirectionalLight { base_light: self.base, ..Default::default() }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpotLightDescription {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub base: GPUBaseLight,
+    pub atten: GPUAttenuation,
+    /// Half angles of the cone, in degrees.
+    pub inner_angle: f32,
+    pub outer_angle: f32
+}
+
+impl Default for SpotLightDescription {
+    fn default() -> Self {
+        Self::from_light(&SpotLight::default())
+    }
+}
+
+impl SpotLightDescription {
+    pub fn from_light(light: &SpotLight) -> Self {
+        Self {
+            position: light.transform.translation,
+            direction: light.transform.rotation * -Vec3::Z,
+            base: light.gpu_light.base_light,
+            atten: light.gpu_light.atten,
+            inner_angle: light.inner_angle.to_degrees(),
+            outer_angle: light.outer_angle.to_degrees()
+        }
+    }
+
+    pub fn to_light(&self) -> SpotLight {
+        SpotLight {
+            transform: Transform { translation: self.position, rotation: rotation_towards(self.direction), ..Default::default() },
+            gpu_light: GPUSpotLight { base_light: self.base, atten: self.atten, ..Default::default() },
+            inner_angle: self.inner_angle.to_radians(),
+            outer_angle: self.outer_angle.to_radians()
+        }
+    }
+}
+
+/// Rotates the lights' local -Z onto `direction`.
+fn rotation_towards(direction: Vec3) -> Quat {
+    Quat::from_rotation_arc(-Vec3::Z, direction.try_normalize().unwrap_or(-Vec3::Z))
+}
+
+/// A serializable `Scene`: the atlas contents, camera, sprites and lights.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub textures: Vec<TextureDescription>,
+    /// Aseprite JSON export packed into the atlas as `sheet/<frame>` regions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprite_sheet: Option<String>,
+    #[serde(default)]
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub sprites: Vec<SpriteDescription>,
+    #[serde(default)]
+    pub point_lights: Vec<PointLightDescription>,
+    #[serde(default)]
+    pub directional_lights: Vec<DirectionalLightDescription>,
+    #[serde(default)]
+    pub spot_lights: Vec<SpotLightDescription>,
+    #[serde(default)]
+    pub ambient: AmbientLight,
+    #[serde(default)]
+    pub tonemap: TonemapSettings,
+    #[serde(default)]
+    pub bloom: BloomSettings
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    Ron,
+    Json
+}
+
+impl SceneFormat {
+    /// Picks the format from a `.ron` or `.json` extension.
+    pub fn from_path(path: &Path) -> Result<Self, SceneError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ron") => Ok(SceneFormat::Ron),
+            Some("json") => Ok(SceneFormat::Json),
+            _ => Err(SceneError::UnknownFormat { path: path.to_path_buf() })
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: PathBuf, error: std::io::Error },
+    Ron(ron::error::SpannedError),
+    RonWrite(ron::Error),
+    Json(serde_json::Error),
+    UnknownFormat { path: PathBuf },
+    Asset(AssetError),
+    Atlas(AtlasError),
+    SpriteSheet(SpriteSheetError),
+    /// A sprite refers to a region no texture was packed under.
+    UnknownRegion { region: String }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneError::Ron(error) => write!(f, "invalid scene ron: {}", error),
+            SceneError::RonWrite(error) => write!(f, "could not write scene ron: {}", error),
+            SceneError::Json(error) => write!(f, "invalid scene json: {}", error),
+            SceneError::UnknownFormat { path } => write!(f, "{}: scene files must end in .ron or .json", path.display()),
+            SceneError::Asset(error) => write!(f, "{}", error),
+            SceneError::Atlas(error) => write!(f, "{}", error),
+            SceneError::SpriteSheet(error) => write!(f, "{}", error),
+            SceneError::UnknownRegion { region } => write!(f, "scene sprite uses unknown region {}", region)
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<AssetError> for SceneError {
+    fn from(error: AssetError) -> Self {
+        SceneError::Asset(error)
+    }
+}
+
+impl From<AtlasError> for SceneError {
+    fn from(error: AtlasError) -> Self {
+        SceneError::Atlas(error)
+    }
+}
+
+impl From<SpriteSheetError> for SceneError {
+    fn from(error: SpriteSheetError) -> Self {
+        SceneError::SpriteSheet(error)
+    }
+}
+
+impl SceneDescription {
+    /// Whether `path` (resolved against the asset directory) is one of the
+    /// files the atlas is packed from, so editing it needs a `reload_atlas`.
+    pub fn uses_atlas_source(&self, assets: &AssetManager, path: &Path) -> bool {
+        let textures = self.textures.iter()
+            .flat_map(|texture| std::iter::once(&texture.albedo).chain(texture.normal.as_ref()))
+            .map(|source| assets.resolve(source));
+        let sheet = self.sprite_sheet.iter().flat_map(|sheet| {
+            let (image_path, normal_path) = sprite_sheet_images(sheet);
+            [assets.resolve(sheet), assets.resolve(&image_path.to_string_lossy()), assets.resolve(&normal_path.to_string_lossy())]
+        });
+        textures.chain(sheet).any(|source| source == path)
+    }
+
+    pub fn parse(source: &str, format: SceneFormat) -> Result<Self, SceneError> {
+        match format {
+            SceneFormat::Ron => ron::from_str(source).map_err(SceneError::Ron),
+            SceneFormat::Json => serde_json::from_str(source).map_err(SceneError::Json)
+        }
+    }
+
+    pub fn to_string(&self, format: SceneFormat) -> Result<String, SceneError> {
+        match format {
+            SceneFormat::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(SceneError::RonWrite),
+            SceneFormat::Json => serde_json::to_string_pretty(self).map_err(SceneError::Json)
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SceneError> {
+        let format = SceneFormat::from_path(path)?;
+        let source = std::fs::read_to_string(path).map_err(|error| SceneError::Io { path: path.to_path_buf(), error })?;
+        Self::parse(&source, format)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SceneError> {
+        let source = self.to_string(SceneFormat::from_path(path)?)?;
+        std::fs::write(path, source).map_err(|error| SceneError::Io { path: path.to_path_buf(), error })
+    }
+}
+
+/// The material sprites in a scene use unless they are given another: the
+/// atlas has no specular map, so these attributes apply unscaled.
+pub fn default_specular_attributes() -> GPUSpecularAttributes {
+    GPUSpecularAttributes { power: 32.0, intensity: 0.5 }
+}
+
+/// What a `Renderer` draws: the camera, sprites and lights, and the atlas
+/// the sprites' regions come from.
+pub struct Scene {
+    /// What the scene was loaded from; `reset` goes back to it.
+    pub description: SceneDescription,
+    pub camera: Camera,
+    pub sprites: Vec<Sprite>,
+    pub lights: Vec<PointLight>,
+    pub directional_lights: Vec<DirectionalLight>,
+    pub spot_lights: Vec<SpotLight>,
+    pub ambient: AmbientLight,
+    /// How the HDR scene color is mapped to the target.
+    pub tonemap: TonemapSettings,
+    /// Glow around light brighter than the threshold, added before tonemapping.
+    pub bloom: BloomSettings,
+    pub atlas: TextureAtlas,
+    /// Material drawing from the sprite atlas.
+    pub atlas_material: MaterialHandle,
+    /// Clips from the description's sprite sheet.
+    pub sprite_sheet_clips: Vec<AnimationClip>,
+    /// Clip end events from the last `update_animations`, by sprite index.
+    pub animation_events: Vec<(usize, AnimationEvent)>
+}
+
+impl Scene {
+    /// Packs the description's textures into an atlas, adds the atlas
+    /// material to the renderer and builds the camera, sprites and lights.
+    /// A scene being replaced should be passed to `unload` so its material
+    /// and atlas are freed.
+    pub fn load(renderer: &mut Renderer, description: SceneDescription) -> Result<Self, SceneError> {
+        let packed = pack_atlas(renderer, &description)?;
+        let material = Material::new(packed.albedo)
+            .with_normal(packed.normal)
+            .with_specular_attributes(default_specular_attributes());
+        let atlas_material = renderer.materials.add(material);
+        renderer.assets.free_unused();
+
+        let mut scene = Self {
+            camera: description.camera.to_camera(),
+            description,
+            sprites: Vec::new(),
+            lights: Vec::new(),
+            directional_lights: Vec::new(),
+            spot_lights: Vec::new(),
+            ambient: AmbientLight::default(),
+            tonemap: TonemapSettings::default(),
+            bloom: BloomSettings::default(),
+            atlas: packed.atlas,
+            atlas_material,
+            sprite_sheet_clips: packed.clips,
+            animation_events: Vec::new()
+        };
+        scene.reset()?;
+        Ok(scene)
+    }
+
+    /// Removes the atlas material from the renderer and frees the atlas
+    /// textures, unless something else still holds them.
+    pub fn unload(self, renderer: &mut Renderer) {
+        renderer.materials.remove(self.atlas_material);
+        renderer.assets.free_unused();
+    }
+
+    /// Rebuilds the camera, sprites and lights from the description,
+    /// discarding any changes made since.
+    pub fn reset(&mut self) -> Result<(), SceneError> {
+        let description = &self.description;
+        self.camera = description.camera.to_camera();
+        self.sprites = description.sprites.iter().map(|sprite_description| {
+            let mut sprite = Sprite::from_region(&self.atlas, &sprite_description.region)
+                .ok_or_else(|| SceneError::UnknownRegion { region: sprite_description.region.clone() })?;
+            sprite.transform = sprite_description.transform.to_transform();
+            sprite.material = self.atlas_material;
+            Ok(sprite)
+        }).collect::<Result<_, SceneError>>()?;
+        self.lights = description.point_lights.iter().map(PointLightDescription::to_light).collect();
+        self.directional_lights = description.directional_lights.iter().map(DirectionalLightDescription::to_light).collect();
+        self.spot_lights = description.spot_lights.iter().map(SpotLightDescription::to_light).collect();
+        self.ambient = description.ambient;
+        self.tonemap = description.tonemap;
+        self.bloom = description.bloom;
+        Ok(())
+    }
+
+    /// Repacks the atlas from the description's files, points the atlas
+    /// material at it and moves every sprite to its region in the new layout.
+    pub fn reload_atlas(&mut self, renderer: &mut Renderer) -> Result<(), SceneError> {
+        let packed = pack_atlas(renderer, &self.description)?;
+        if let Some(material) = renderer.materials.get_mut(self.atlas_material) {
+            material.albedo = packed.albedo;
+            material.normal = Some(packed.normal);
+        }
+        renderer.assets.free_unused();
+        for sprite in self.sprites.iter_mut() {
+            if let Some(name) = sprite.region.as_ref().map(|region| region.name.clone()) {
+                sprite.set_region(&packed.atlas, &name);
+            }
+        }
+        self.atlas = packed.atlas;
+        self.sprite_sheet_clips = packed.clips;
+        Ok(())
+    }
+
+    /// Steps every animated sprite by `dt` seconds. Returns true if any of
+    /// them changed frame, so the sprites need preparing again.
+    pub fn update_animations(&mut self, dt: f32) -> bool {
+        self.animation_events.clear();
+        let mut changed = false;
+        for (index, sprite) in self.sprites.iter_mut().enumerate() {
+            if sprite.animation.is_none() {
+                continue;
+            }
+            let region = sprite.region.as_ref().map(|region| region.name.clone());
+            self.animation_events.extend(sprite.animate(&self.atlas, dt).into_iter().map(|event| (index, event)));
+            changed |= sprite.region.as_ref().map(|region| &region.name) != region.as_ref();
+        }
+        changed
+    }
+
+    /// Replaces the sprites with a grid of `count` copies of the first
+    /// texture, for profiling the batch renderer. Does nothing if the scene
+    /// has no textures.
+    pub fn fill_sprite_grid(&mut self, count: usize) {
+        let name = match self.description.textures.first() {
+            Some(texture) => &texture.name,
+            None => return
+        };
+        let region_size = self.atlas.region(name).unwrap().size;
+        let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = count.div_ceil(columns).max(1);
+        let spacing = 100.0 / columns.max(rows) as f32;
+        let scale = spacing / region_size.max_element() as f32;
+        self.sprites = (0..count).map(|i| {
+            let column = (i % columns) as f32 - (columns - 1) as f32 * 0.5;
+            let row = (i / columns) as f32 - (rows - 1) as f32 * 0.5;
+            let mut sprite = Sprite::from_region(&self.atlas, name).unwrap();
+            sprite.transform = Transform {
+                translation: vec3(column * spacing, row * spacing, 0.0),
+                scale: vec3(scale, scale, 1.0),
+                ..Default::default()
+            };
+            sprite.material = self.atlas_material;
+            sprite
+        }).collect();
+    }
+
+    /// Describes the scene as it is now. Animated sprites are left out, as
+    /// their regions come from the sprite sheet rather than the scene.
+    pub fn describe(&self) -> SceneDescription {
+        SceneDescription {
+            textures: self.description.textures.clone(),
+            sprite_sheet: self.description.sprite_sheet.clone(),
+            camera: CameraDescription::from_camera(&self.camera),
+            sprites: self.sprites.iter()
+                .filter(|sprite| sprite.animation.is_none())
+                .filter_map(|sprite| sprite.region.as_ref().map(|region| SpriteDescription {
+                    region: region.name.clone(),
+                    transform: TransformDescription::from(&sprite.transform)
+                }))
+                .collect(),
+            point_lights: self.lights.iter().map(PointLightDescription::from_light).collect(),
+            directional_lights: self.directional_lights.iter().map(DirectionalLightDescription::from_light).collect(),
+            spot_lights: self.spot_lights.iter().map(SpotLightDescription::from_light).collect(),
+            ambient: self.ambient,
+            tonemap: self.tonemap,
+            bloom: self.bloom
+        }
+    }
+}
+
+struct PackedAtlas {
+    atlas: TextureAtlas,
+    albedo: Handle<Texture>,
+    normal: Handle<Texture>,
+    clips: Vec<AnimationClip>
+}
+
+/// Packs the description's textures and sprite sheet into an atlas and uploads it.
+fn pack_atlas(renderer: &mut Renderer, description: &SceneDescription) -> Result<PackedAtlas, SceneError> {
+    let assets = &mut renderer.assets;
+    let mut atlas_builder = AtlasBuilder::new(ATLAS_PADDING)
+        .with_max_size(renderer.device.limits().max_texture_dimension_2d)
+        .with_normal_convention(default_normal_convention());
+    for texture in description.textures.iter() {
+        let albedo = assets.load_image(&texture.albedo)?;
+        let normal = texture.normal.as_ref().map(|normal| assets.load_image(normal)).transpose()?;
+        atlas_builder.add_with_convention(&texture.name, &albedo, normal.as_ref(), texture.normal_convention)?;
+    }
+    let clips = match description.sprite_sheet.as_ref() {
+        Some(path) => load_sprite_sheet(assets, &mut atlas_builder, path)?,
+        None => Vec::new()
+    };
+    let built_atlas = atlas_builder.build()?;
+    let options = TextureOptions::default()
+        .with_sampler(assets.supported_sampler(assets.sampler))
+        .with_mipmaps(assets.mipmaps.is_some());
+    let (albedo, normal) = built_atlas.upload(&renderer.device, &renderer.queue, "sprite_atlas", &options, &mut assets.samplers, assets.mipmaps.as_mut());
+    Ok(PackedAtlas {
+        atlas: built_atlas.atlas,
+        albedo: assets.textures.add(albedo),
+        normal: assets.textures.add(normal),
+        clips
+    })
+}
+
+/// The albedo and normal sheet images beside an Aseprite JSON export.
+fn sprite_sheet_images(path: &str) -> (PathBuf, PathBuf) {
+    let image_path = Path::new(path).with_extension("png");
+    let normal_path = image_path.with_file_name(format!("{}_normal.png", image_path.file_stem().unwrap().to_string_lossy()));
+    (image_path, normal_path)
+}
+
+/// Packs the frames of an Aseprite export into the atlas. The sheet image is
+/// the JSON path with a png extension, and an optional `_normal` suffixed png
+/// beside it supplies the normal frames.
+fn load_sprite_sheet(assets: &mut AssetManager, atlas_builder: &mut AtlasBuilder, path: &str) -> Result<Vec<AnimationClip>, SceneError> {
+    let json = assets.read(path)?;
+    let sheet = AsepriteSheet::from_json(&String::from_utf8_lossy(&json))?;
+    let (image_path, normal_path) = sprite_sheet_images(path);
+    let albedo = assets.load_image(&image_path.to_string_lossy())?;
+    let normal = match assets.resolve(&normal_path.to_string_lossy()).exists() {
+        true => Some(assets.load_image(&normal_path.to_string_lossy())?),
+        false => None
+    };
+    sheet.add_to_atlas(atlas_builder, "sheet", &albedo, normal.as_ref())?;
+    Ok(sheet.clips("sheet")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"(
+        textures: [
+            (name: "bump", albedo: "bump_diffuse.png", normal: Some("bump_normal.png")),
+            (name: "rg", albedo: "bump_diffuse.png", normal: Some("rg_normal.png"), normal_convention: TwoChannel(y_down: false)),
+        ],
+        camera: (resize: FixedHeight(0.72), zoom: 101.0),
+        sprites: [(region: "bump", transform: (scale: (0.1, 0.1, 1.1)))],
+        point_lights: [(position: (0.0, 0.0, 1.0), base: (diffuse_intensity: 50.0))],
+        spot_lights: [(direction: (1.0, 0.0, 0.0), outer_angle: 45.0)],
+        ambient: Hemisphere(sky: (0.4, 0.5, 1.0), ground: (0.2, 0.1, 0.0), up: (0.0, 1.0, 0.0), intensity: 0.3),
+        tonemap: (tonemapper: Reinhard),
+        bloom: (enabled: true, threshold: 2.0),
+    )"#;
+
+    #[test]
+    fn omitted_fields_take_defaults() {
+        let scene = SceneDescription::parse(SCENE, SceneFormat::Ron).unwrap();
+        assert_eq!(scene.camera.position, vec3(0.0, 0.0, 0.1));
+        assert_eq!(scene.textures[0].normal_convention, NormalMapConvention::YDown);
+        assert_eq!(scene.textures[1].normal_convention, NormalMapConvention::TwoChannel { y_down: false });
+        assert_eq!(scene.sprites[0].transform.rotation, Quat::IDENTITY);
+        assert_eq!(scene.point_lights[0].base.color, Vec3::ONE);
+        assert_eq!(scene.point_lights[0].atten, GPUAttenuation::default());
+        assert_eq!(scene.spot_lights[0].inner_angle, 20.0);
+        assert!(scene.directional_lights.is_empty());
+        assert_eq!(scene.tonemap, TonemapSettings { tonemapper: crate::render::Tonemapper::Reinhard, exposure: 0.0 });
+        assert_eq!(scene.bloom, BloomSettings { enabled: true, threshold: 2.0, ..BloomSettings::default() });
+    }
+
+    #[test]
+    fn only_atlas_sources_repack_the_atlas() {
+        let mut scene = SceneDescription::parse(SCENE, SceneFormat::Ron).unwrap();
+        scene.sprite_sheet = Some("sheets/walk.json".to_string());
+        let assets = AssetManager::new("res");
+        for source in ["bump_normal.png", "rg_normal.png", "sheets/walk.json", "sheets/walk.png", "sheets/walk_normal.png"] {
+            assert!(scene.uses_atlas_source(&assets, &assets.resolve(source)), "{}", source);
+        }
+        assert!(!scene.uses_atlas_source(&assets, &assets.resolve("frag.wgsl")));
+        assert!(!scene.uses_atlas_source(&assets, Path::new("bump_normal.png")));
+    }
+
+    #[test]
+    fn ron_and_json_round_trip() {
+        let scene = SceneDescription::parse(SCENE, SceneFormat::Ron).unwrap();
+        for format in [SceneFormat::Ron, SceneFormat::Json] {
+            let text = scene.to_string(format).unwrap();
+            assert_eq!(SceneDescription::parse(&text, format).unwrap(), scene);
+        }
+    }
+
+    #[test]
+    fn light_directions_survive_conversion() {
+        let scene = SceneDescription::parse(SCENE, SceneFormat::Ron).unwrap();
+        let mut light = scene.spot_lights[0].to_light();
+        light.sync();
+        assert!(light.gpu_light.direction.abs_diff_eq(Vec3::X, 1e-5));
+        let description = SpotLightDescription::from_light(&light);
+        assert!(description.direction.abs_diff_eq(Vec3::X, 1e-5));
+        assert!((description.outer_angle - 45.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bundled_scene_loads() {
+        let scene = SceneDescription::load(&crate::render::AssetManager::default().resolve(DEFAULT_SCENE)).unwrap();
+        assert_eq!(scene.textures[0].name, "bump");
+        assert_eq!(scene.sprites[0].region, "bump");
+        assert_eq!(scene.camera.zoom, 101.0);
+    }
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(SceneFormat::from_path(Path::new("a/scene.ron")).unwrap(), SceneFormat::Ron);
+        assert_eq!(SceneFormat::from_path(Path::new("scene.json")).unwrap(), SceneFormat::Json);
+        assert!(SceneFormat::from_path(Path::new("scene.toml")).is_err());
+    }
+}